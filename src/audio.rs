@@ -0,0 +1,271 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const TONE_FREQUENCY: f32 = 440.0;
+pub const FRAME_RATE: u32 = 60; // the sound timer is decremented at 60Hz by default
+
+
+/// What the buzzer plays while the sound timer is non-zero.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tone {
+    Square,                                   // fixed pitch square wave
+    Pattern { pattern: [u8; 16], pitch: u8 }, // XO-CHIP 128-bit pattern, played MSB first
+}
+
+
+impl Tone {
+    /// Playback rate of an XO-CHIP pattern, in bits per second.
+    pub fn pattern_rate(pitch: u8) -> f32 {
+        4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+
+/// Sink for the CHIP-8 buzzer.
+///
+/// `frame` is called once per timer tick (1/60 s by default) with the tone to be heard
+/// during that tick, `None` when the sound timer is zero.
+pub trait AudioBackend {
+    fn frame(&mut self, tone: Option<Tone>) -> io::Result<()>;
+}
+
+
+/// Square wave tone generator.
+#[derive(Clone, Debug)]
+pub struct SquareWave {
+    pub frequency: f32,
+    pub volume: f32, // amplitude, from 0 to 1
+    sample_rate: u32,
+    phase: f32,      // position in the current period, from 0 to 1
+}
+
+
+impl SquareWave {
+    pub fn new(frequency: f32, sample_rate: u32) -> SquareWave {
+        SquareWave {
+            frequency,
+            volume: 0.25,
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    /// Fills `out` with the next samples of the tone.
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let step = self.frequency / self.sample_rate as f32;
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+
+/// Player for XO-CHIP audio patterns.
+#[derive(Clone, Debug)]
+pub struct PatternWave {
+    pub volume: f32,
+    sample_rate: u32,
+    position: f32, // current bit in the pattern, from 0 to 128
+}
+
+
+impl PatternWave {
+    pub fn new(sample_rate: u32) -> PatternWave {
+        PatternWave {
+            volume: 0.25,
+            sample_rate,
+            position: 0.0,
+        }
+    }
+
+    /// Fills `out` with the next samples of `pattern` played at `pitch`.
+    pub fn fill(&mut self, pattern: &[u8; 16], pitch: u8, out: &mut [f32]) {
+        let step = Tone::pattern_rate(pitch) / self.sample_rate as f32;
+        for sample in out.iter_mut() {
+            let bit = self.position as usize;
+            let high = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if high { self.volume } else { -self.volume };
+            self.position = (self.position + step) % 128.0;
+        }
+    }
+}
+
+
+/// Discards all sound.
+pub struct NullAudio;
+
+
+impl AudioBackend for NullAudio {
+    fn frame(&mut self, _tone: Option<Tone>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// Rings the terminal bell (BEL) each time a tone starts.
+pub struct BellAudio<W: Write> {
+    out: W,
+    playing: bool,
+}
+
+
+impl<W: Write> BellAudio<W> {
+    pub fn new(out: W) -> BellAudio<W> {
+        BellAudio { out, playing: false }
+    }
+}
+
+
+impl<W: Write> AudioBackend for BellAudio<W> {
+    fn frame(&mut self, tone: Option<Tone>) -> io::Result<()> {
+        let playing = tone.is_some();
+        if playing && !self.playing {
+            self.out.write_all(b"\x07")?;
+            self.out.flush()?;
+        }
+        self.playing = playing;
+        Ok(())
+    }
+}
+
+
+/// Renders the tone into a 16-bit mono PCM WAV stream, silence included, so that the
+/// sound of a session can be checked without a sound card.
+pub struct WavAudio<W: Write + Seek> {
+    out: W,
+    wave: SquareWave,
+    pattern_wave: PatternWave,
+    samples: Vec<f32>,
+    data_len: u32, // bytes of sample data written so far
+}
+
+
+impl<W: Write + Seek> WavAudio<W> {
+    /// `frame_rate` is the number of timer ticks per second.
+    pub fn new(mut out: W, sample_rate: u32, frame_rate: u32) -> io::Result<WavAudio<W>> {
+        write_wav_header(&mut out, sample_rate, 0)?;
+        Ok(WavAudio {
+            out,
+            wave: SquareWave::new(TONE_FREQUENCY, sample_rate),
+            pattern_wave: PatternWave::new(sample_rate),
+            samples: vec![0.0; (sample_rate / frame_rate) as usize],
+            data_len: 0,
+        })
+    }
+
+    /// Patches the sizes in the header. Called on drop, errors included.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.out, self.wave.sample_rate, self.data_len)?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()
+    }
+}
+
+
+impl<W: Write + Seek> AudioBackend for WavAudio<W> {
+    fn frame(&mut self, tone: Option<Tone>) -> io::Result<()> {
+        match tone {
+            Some(Tone::Square) => self.wave.fill(&mut self.samples),
+            Some(Tone::Pattern { pattern, pitch }) => self.pattern_wave.fill(&pattern, pitch, &mut self.samples),
+            None => self.samples.fill(0.0),
+        }
+
+        let mut bytes = Vec::with_capacity(self.samples.len() * 2);
+        for sample in &self.samples {
+            bytes.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+        }
+        self.out.write_all(&bytes)?;
+        self.data_len += bytes.len() as u32;
+        Ok(())
+    }
+}
+
+
+impl<W: Write + Seek> Drop for WavAudio<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+
+fn write_wav_header<W: Write>(out: &mut W, sample_rate: u32, data_len: u32) -> io::Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;                                // fmt chunk size
+    out.write_all(&1u16.to_le_bytes())?;                                 // PCM
+    out.write_all(&channels.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;   // byte rate
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&bits_per_sample.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    pub fn square_wave_test() {
+        let mut wave = SquareWave::new(1000.0, 8000);
+        let mut out = [0.0; 16];
+        wave.fill(&mut out);
+        // 8 samples per period: 4 high, 4 low
+        assert_eq!(&out[..8], &[0.25, 0.25, 0.25, 0.25, -0.25, -0.25, -0.25, -0.25]);
+        assert_eq!(out[..8], out[8..]);
+    }
+
+    #[test]
+    pub fn pattern_wave_test() {
+        // pitch 64 plays 4000 bits per second: 2 samples per bit at 8000Hz
+        let mut wave = PatternWave::new(8000);
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1010_0000;
+        let mut out = [0.0; 6];
+        wave.fill(&pattern, 64, &mut out);
+        assert_eq!(out, [0.25, 0.25, -0.25, -0.25, 0.25, 0.25]);
+    }
+
+    #[test]
+    pub fn wav_audio_test() {
+        let mut buf = Cursor::new(Vec::new());
+        {
+            let mut wav = WavAudio::new(&mut buf, 6000, FRAME_RATE).unwrap();
+            wav.frame(None).unwrap();
+            wav.frame(Some(Tone::Square)).unwrap();
+        }
+        let bytes = buf.into_inner();
+
+        // two frames of 100 samples, 2 bytes each
+        assert_eq!(bytes.len(), 44 + 400);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 400);
+        assert!(bytes[44..244].iter().all(|&b| b == 0));
+        assert!(bytes[244..].iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    pub fn bell_audio_test() {
+        let mut out = Vec::new();
+        {
+            let mut bell = BellAudio::new(&mut out);
+            for tone in [None, Some(Tone::Square), Some(Tone::Square), None, Some(Tone::Square)] {
+                bell.frame(tone).unwrap();
+            }
+        }
+        assert_eq!(out, b"\x07\x07");
+    }
+}
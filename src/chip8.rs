@@ -1,74 +1,236 @@
-use std::error::Error;
+use crate::{Chip8Error, Keypad, MemoryPolicy, Platform, Quirks, RomDatabase, RomInfo, Stack};
+use crate::font::{FontSet, FONT_START};
+use crate::opcodes::UnknownOpcodePolicy;
+use crate::rng::{RandomSource, SplitMix64};
+use crate::audio::Tone;
 
 
+pub const DISPLAY_WIDTH: usize = 128;  // size of display_buf, the hi-res SUPER-CHIP screen
+pub const DISPLAY_HEIGHT: usize = 64;
+pub const PROGRAM_START: usize = 0x200;
+
+
+#[derive(Clone)]
 pub struct Chip8 {
-    pub memory: [u8; 4096],            // 4KB of memory for the program (writable).
-    pub display_buf: [[bool; 32]; 64], // 2D pixel array
+    pub memory: Vec<u8>,               // 4KB (64KB on XO-CHIP) of memory for the program (writable).
+    pub memory_policy: MemoryPolicy,   // what happens on accesses past the end of memory
+    pub platform: Platform,            // CHIP-8 variant being interpreted
+    pub quirks: Quirks,                // behaviour of the ambiguous instructions
+    pub display_buf: [[u8; DISPLAY_HEIGHT]; DISPLAY_WIDTH], // 2D pixel array of bitplane masks, only width() x height() is visible
+    pub hires: bool,                   // SUPER-CHIP 128x64 mode, 64x32 otherwise
+    pub planes: u8,                    // XO-CHIP bitplanes affected by drawing (FN01), bit 0 is plane 1
     pub pc: u16,                       // Program Counter: Points at current instruction in memory
     pub i: u16,                        // Index register: Points at locations in memory
-    pub stack: Vec<u16>,               // Stack used to call functions/subroutines and return from them
+    pub stack: Stack,                  // Stack used to call functions/subroutines and return from them
     pub delay_timer: u32,              // decreases at 60Hz
-    pub sound_timer: u32,              // beeps when value is 0, decreases at 60Hz
+    pub sound_timer: u32,              // buzzer sounds while non-zero, decreases at 60Hz
     pub v: [u8; 16],                   // 16 8-bit registers, from V0 to VF. VF is often used as a flag register.
-    pub font_location: u16,            // Starting point of the stored fonts in memory
+    pub font: FontSet,                 // glyphs written by load_font
+    pub font_location: u16,            // Starting point of the 4x5 font in memory (FX29)
+    pub big_font_location: u16,        // Starting point of the 8x10 font (FX30)
+    pub rpl: [u8; 16],                 // SUPER-CHIP RPL user flags (FX75/FX85)
+    pub halted: bool,                  // set by 00FD (SUPER-CHIP exit)
+    pub unknown_opcode_policy: UnknownOpcodePolicy, // what 0NNN and invalid instructions do
+    pub trapped: Option<u16>,          // address of the instruction that trapped, if any
+    pub audio_pattern: Option<[u8; 16]>, // XO-CHIP 1-bit audio pattern (F002), square wave if None
+    pub pitch: u8,                     // XO-CHIP playback rate of the audio pattern (FX3A)
     pub draw_flag: bool,                // true if current opcode has changed the display buffer
+    pub vblank: bool,                  // true until a sprite is drawn in the current frame (display wait quirk)
+    pub rng: Box<dyn RandomSource>,    // source of CXNN, seeded from the OS unless seed_rng is called
+    pub keypad: Keypad,                // hex keypad state, written by the frontend
+    pub key_wait: Option<KeyWait>,     // Some while blocked on FX0A
     pub timers_dec_flag: bool
 }
 
 
+/// State of an FX0A in progress.
+///
+/// Like the COSMAC VIP, FX0A blocks until a key is pressed and then released, and only
+/// then stores it in Vx. Timers keep running while blocked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyWait {
+    pub register: usize,     // Vx receiving the key
+    pub pressed: Option<u8>, // key pressed so far, stored once released
+}
+
+
 impl Chip8 {
 
     pub fn init() -> Chip8 {
+        Chip8::with_platform(Platform::Chip8)
+    }
+
+    pub fn with_platform(platform: Platform) -> Chip8 {
         let mut chip8 = Chip8 {
-            memory: [0; 4096],
-            display_buf: [[false; 32]; 64],
-            pc: 0x200,
+            memory: vec![0; platform.memory_size()],
+            memory_policy: MemoryPolicy::default(),
+            platform,
+            quirks: Quirks::for_platform(platform),
+            display_buf: [[0; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
+            hires: false,
+            planes: 1,
+            pc: PROGRAM_START as u16,
             i: 0,
-            stack: Vec::with_capacity(32),
+            stack: Stack::new(platform.stack_depth()),
             delay_timer: 0,
             sound_timer: 0,
             v: [0; 16],
-            font_location: 0x200,
+            font: FontSet::default(),
+            font_location: FONT_START,
+            big_font_location: FONT_START,
+            rpl: [0; 16],
+            halted: false,
+            unknown_opcode_policy: UnknownOpcodePolicy::default(),
+            trapped: None,
+            audio_pattern: None,
+            pitch: 64,
             draw_flag: false,
+            vblank: true,
+            rng: Box::new(SplitMix64::from_entropy()),
+            keypad: Keypad::new(),
+            key_wait: None,
             timers_dec_flag: false,
         };
 
-        chip8.init_font();
+        chip8.load_font(FontSet::default(), FONT_START).expect("the font fits below the program");
         chip8
     }
 
-    fn init_font(self: &mut Self) {
-        let font = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-    
-        self.memory[0x50..=0x09F].clone_from_slice(&font[..]);
+    /// Switches to `platform` and its default quirks and stack depth, keeping the memory
+    /// contents.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.quirks = Quirks::for_platform(platform);
+        self.stack.set_depth(platform.stack_depth());
+        self.memory.resize(platform.memory_size(), 0);
     }
 
-    pub fn load_data(self: &mut Self, path: &str) -> Result<(), Box<dyn Error>> {
+    /// Loads the ROM at `path`. The platform is `platform` if given (`--platform`), else the
+    /// one `db` knows the ROM by, whose quirks are then selected too. Returns the database
+    /// entry. The machine is left unchanged if the ROM doesn't fit that platform's memory.
+    pub fn load_data(&mut self, path: &str, db: &RomDatabase, platform: Option<Platform>) -> Result<Option<RomInfo>, Chip8Error> {
         let mem = std::fs::read(path)?;
+        let info = db.lookup(&mem).cloned();
+        let target = platform.or(info.as_ref().map(|info| info.platform));
         let mem_len = mem.len();
-        self.memory[0x200..0x200+mem_len].clone_from_slice(&mem[..]);
+        let max = target.map_or(self.memory.len(), |platform| platform.memory_size()) - PROGRAM_START;
+        if mem_len > max {
+            return Err(Chip8Error::RomTooLarge { size: mem_len, max });
+        }
+        if let Some(target) = target {
+            self.set_platform(target);
+        }
+        if let (Some(info), None) = (&info, platform) {
+            self.quirks = info.quirks;
+        }
+        self.memory[PROGRAM_START..PROGRAM_START+mem_len].clone_from_slice(&mem[..]);
+        Ok(info)
+    }
 
+    /// Makes CXNN deterministic, for tests and replays.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng.seed(seed);
+    }
+
+    /// Fetches and executes one instruction. On error, PC is left on the failing
+    /// instruction.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.pc;
+        let opcode = self.fetch_opcode()?;
+        self.execute_opcode(opcode).inspect_err(|_| self.pc = pc)
+    }
+
+    /// Runs one 1/60 s frame: up to `cycles` instructions, fewer if the interpreter halts
+    /// or traps, then a single tick of the timers.
+    pub fn run_frame(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            if self.halted || self.trapped.is_some() {
+                break;
+            }
+            self.step()?;
+        }
+        if self.trapped.is_none() {
+            self.tick_timers();
+        }
         Ok(())
     }
 
-    pub fn beep_sound(&self) {
-        // TODO
+    /// true while FX0A is waiting for a key press and release
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    /// Decrements the delay and sound timers and starts a new frame. Must be called at 60Hz.
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// true while the buzzer should sound
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// What the buzzer plays right now, None when silent.
+    pub fn tone(&self) -> Option<Tone> {
+        if !self.is_sound_playing() {
+            return None;
+        }
+        match self.audio_pattern {
+            Some(pattern) => Some(Tone::Pattern { pattern, pitch: self.pitch }),
+            None => Some(Tone::Square),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn run_frame_test() {
+        let mut chip8 = Chip8::init();
+        // 7001 (V0 += 1) over and over, then a jump back
+        for addr in (0x200..0x210).step_by(2) {
+            chip8.memory[addr..addr + 2].copy_from_slice(&[0x70, 0x01]);
+        }
+        chip8.memory[0x210..0x212].copy_from_slice(&[0x12, 0x00]);
+        chip8.delay_timer = 5;
+
+        chip8.run_frame(5).unwrap();
+        assert_eq!((chip8.v[0], chip8.pc, chip8.delay_timer), (5, 0x20A, 4));
+
+        // 00FD stops the frame early
+        chip8.platform = Platform::SuperChip;
+        chip8.memory[0x20C..0x20E].copy_from_slice(&[0x00, 0xFD]);
+        chip8.run_frame(100).unwrap();
+        assert!(chip8.halted);
+        assert_eq!((chip8.v[0], chip8.delay_timer), (6, 3));
+    }
+
+    #[test]
+    pub fn load_data_test() {
+        // a ROM too large for CHIP-8, known by the database as an XO-CHIP one
+        let rom = vec![0x12; 0x1000];
+        let hash: String = crate::romdb::sha1(&rom).iter().map(|byte| format!("{:02x}", byte)).collect();
+        let db = RomDatabase::parse(&format!(r#"[{{"title": "Big", "roms": {{"{}": {{"platforms": ["xochip"]}}}}}}]"#, hash)).unwrap();
+        let path = std::env::temp_dir().join(format!("chip8emu-load-{}.ch8", std::process::id()));
+        std::fs::write(&path, &rom).unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let mut chip8 = Chip8::init();
+        assert!(chip8.load_data(&path, &db, None).unwrap().is_some());
+        assert_eq!((chip8.platform, chip8.memory.len()), (Platform::XoChip, 0x10000));
+
+        // --platform wins over the database, and a failed load changes nothing
+        let mut chip8 = Chip8::init();
+        chip8.set_platform(Platform::SuperChip);
+        chip8.quirks.shift = false;
+        let err = chip8.load_data(&path, &db, Some(Platform::Chip8)).unwrap_err();
+        assert!(matches!(err, Chip8Error::RomTooLarge { size: 0x1000, max: 0xE00 }));
+        assert_eq!((chip8.platform, chip8.memory.len(), chip8.quirks.shift), (Platform::SuperChip, 0x1000, false));
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,438 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::time::Duration;
+use chip8emu::audio::{self, AudioBackend, BellAudio, NullAudio, WavAudio};
+use chip8emu::stack::{MAX_STACK_DEPTH, VIP_STACK_TOP};
+use chip8emu::debugger::Breakpoint;
+use chip8emu::{Chip8, Chip8Error, FontSet, Keymap, MemoryPolicy, Platform, Quirks, RomDatabase, UnknownOpcodePolicy};
+use chip8emu::font::FONT_START;
+use chip8emu::headless::Limits;
+
+
+const USAGE: &str = "\
+Usage: chip8emu [OPTIONS] <ROM>
+
+Options:
+    --platform <NAME>  CHIP-8 variant of the ROM: chip8, schip or xochip
+                       [default: from the ROM database, or chip8]
+    --quirks <PRESET>  behaviour of the ambiguous instructions: vip, chip48,
+                       schip or xochip [default: from the ROM database, or
+                       the platform's]
+    --quirk <NAME>=<VALUE>
+                       override a single quirk, after --quirks: shift, jump,
+                       vf-reset, clip, display-wait (on/off) or memory
+                       (x+1, x or none)
+    --font <NAME>      hex digit glyphs: vip, eti660, dream6800 or octo
+                       [default: octo]
+    --font-address <HEX>
+                       where the font is stored, the 8x10 font follows the
+                       4x5 one and both must end before 200 [default: 50]
+    --memory <POLICY>  accesses past the end of memory: wrap around like the
+                       hardware, or error to stop [default: wrap]
+    --on-unknown <POLICY>
+                       what machine code calls (0NNN) and invalid opcodes do:
+                       halt with an error, skip them, or trap (stop the
+                       interpreter on them) [default: halt]
+    --stack-depth <N>  nested calls before a stack overflow [default: 12 on
+                       chip8, 16 on schip and xochip]
+    --vip-stack        keep the call stack in memory below 0xED0, like the
+                       COSMAC VIP
+    --rom-db <FILE|none>
+                       programs.json of the chip-8-database to identify the
+                       ROM with, or none [default: the bundled one]
+    --seed <N>         seed of the random numbers of CXNN, for reproducible
+                       runs [default: random]
+    --ipf <N>          instructions per frame [default: from the ROM database,
+                       or 12]
+    --timer-hz <HZ>    frequency of the delay and sound timers, and of the
+                       frames [default: 60]
+    --keymap <LAYOUT|FILE>
+                       keyboard layout: qwerty, azerty or dvorak, or a keymap
+                       config file (`preset = <layout>` and `<hex> = <char>`
+                       lines) [default: azerty]
+    --key <HEX>=<CHAR> bind a hex key to a character, after --keymap
+    --print-keymap     print the keypad mapping and exit
+    --key-hold <MS>    how long a key stays down after its last press when the
+                       terminal does not report key releases [default: 300]
+    --rewind <FRAMES>  frames of history kept for rewinding, 0 to disable
+                       [default: 600]
+    --state <FILE>     start from a save state instead of the ROM's first
+                       instruction
+    --record <FILE>    record the session into a movie file, written on exit
+    --play <FILE>      replay a movie, then hand over to the keyboard; with
+                       --headless, check that it replays without desync
+    --debug            start paused in the debugger, see the controls in the
+                       README
+    --break <ADDR>[ if <COND>]
+                       stop before the instruction at the hex address, when
+                       the condition such as `V3 == 5` holds; enables the
+                       debugger without pausing at the start
+    --audio <OUTPUT>   sound output: none, bell (terminal bell) or wav:<FILE>
+                       to record the buzzer into a WAV file [default: bell]
+    -h, --help         print this help
+
+Headless mode:
+    --headless         run without the terminal, for --frames frames or until
+                       one of the conditions below, then write the outputs
+    --frames <N>       stop after N frames [default: 600 if no other limit]
+    --max-instructions <N>
+                       stop after N instructions
+    --until-self-jump  stop on a jump to itself, the usual end of test ROMs
+    --input <FILE>     keypad script, `<frame> press|release <hex key>` lines
+    --screenshot <FILE>
+                       write the final screen: .pbm, .png, otherwise ASCII
+                       art; `-` for ASCII art on stdout
+    --dump <FILE>      write the registers, stack and memory, `-` for stdout
+";
+
+
+pub struct Options {
+    pub rom_path: String,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub quirk_overrides: Vec<String>, // already validated `--quirk` settings
+    pub font: Option<FontSet>,
+    pub font_address: Option<u16>,
+    pub memory_policy: MemoryPolicy,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+    pub stack_depth: Option<usize>,
+    pub vip_stack: bool,
+    pub rom_db: Option<String>,       // None for the bundled database, Some("none") to disable it
+    pub keymap: Keymap,
+    pub key_hold: Duration,
+    pub seed: Option<u64>,
+    pub ipf: Option<u32>,
+    pub timer_hz: u32,
+    pub audio: AudioOutput,
+    pub rewind_frames: usize,
+    pub state: Option<String>,        // save state to start from
+    pub record: Option<String>,       // movie to record
+    pub play: Option<String>,         // movie to replay
+    pub debug: Option<DebugOptions>,
+    pub headless: Option<HeadlessOptions>,
+}
+
+
+pub struct HeadlessOptions {
+    pub limits: Limits,
+    pub input: Option<String>,      // keypad script path
+    pub screenshot: Option<String>, // `-` for stdout
+    pub dump: Option<String>,       // `-` for stdout
+}
+
+
+pub struct DebugOptions {
+    pub paused: bool, // `--debug`, otherwise only `--break` was given
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+
+pub enum AudioOutput {
+    None,
+    Bell,
+    Wav(String),
+}
+
+
+impl AudioOutput {
+    fn parse(value: &str) -> Result<AudioOutput, String> {
+        match value {
+            "none" => Ok(AudioOutput::None),
+            "bell" => Ok(AudioOutput::Bell),
+            _ => match value.strip_prefix("wav:") {
+                Some(path) if !path.is_empty() => Ok(AudioOutput::Wav(path.to_string())),
+                _ => Err(format!("unknown audio output `{}`", value)),
+            }
+        }
+    }
+
+    /// `frame_rate` is the timer frequency.
+    pub fn open(&self, frame_rate: u32) -> io::Result<Box<dyn AudioBackend>> {
+        Ok(match self {
+            AudioOutput::None => Box::new(NullAudio),
+            AudioOutput::Bell => Box::new(BellAudio::new(io::stdout())),
+            AudioOutput::Wav(path) => Box::new(WavAudio::new(BufWriter::new(File::create(path)?), audio::SAMPLE_RATE, frame_rate)?),
+        })
+    }
+}
+
+
+/// What the command line asks for.
+pub enum Command {
+    Run(Box<Options>),
+    PrintKeymap(Keymap),
+    Help,
+}
+
+
+impl Options {
+    /// Loads the ROM database selected by `--rom-db`.
+    pub fn rom_database(&self) -> Result<RomDatabase, String> {
+        match self.rom_db.as_deref() {
+            None => Ok(RomDatabase::bundled()),
+            Some("none") => Ok(RomDatabase::empty()),
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+                RomDatabase::parse(&text).map_err(|err| format!("{}: {}", path, err))
+            }
+        }
+    }
+
+    /// Applies the platform, quirks, font, error policies, stack settings and seed given on
+    /// the command line, over the ones picked from the ROM database.
+    pub fn configure(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if let Some(platform) = self.platform {
+            chip8.set_platform(platform);
+        }
+        if let Some(quirks) = self.quirks {
+            chip8.quirks = quirks;
+        }
+        for setting in &self.quirk_overrides {
+            chip8.quirks.apply(setting).expect("validated by parse");
+        }
+        chip8.memory_policy = self.memory_policy;
+        chip8.unknown_opcode_policy = self.unknown_opcode_policy;
+        if let Some(depth) = self.stack_depth {
+            chip8.stack.set_depth(depth);
+        }
+        if self.vip_stack {
+            chip8.stack.memory_top = Some(VIP_STACK_TOP);
+        }
+        if let Some(seed) = self.seed {
+            chip8.seed_rng(seed);
+        }
+        if self.font.is_some() || self.font_address.is_some() {
+            chip8.load_font(self.font.unwrap_or_default(), self.font_address.unwrap_or(FONT_START))?;
+        }
+        Ok(())
+    }
+
+    /// Parses the command line, exiting with the usage text on error or `--help`.
+    pub fn from_args() -> Options {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        match Options::parse(&args) {
+            Ok(Command::Run(options)) => *options,
+            Ok(Command::PrintKeymap(keymap)) => {
+                print!("{}", keymap);
+                std::process::exit(0);
+            }
+            Ok(Command::Help) => {
+                print!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Error: {}\n\n{}", err, USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let mut rom_path = None;
+        let mut platform = None;
+        let mut quirks = None;
+        let mut font = None;
+        let mut font_address = None;
+        let mut memory_policy = MemoryPolicy::default();
+        let mut unknown_opcode_policy = UnknownOpcodePolicy::default();
+        let mut stack_depth = None;
+        let mut vip_stack = false;
+        let mut rom_db = None;
+        let mut quirk_overrides = Vec::new();
+        let mut keymap = Keymap::default();
+        let mut key_overrides = Vec::new();
+        let mut print_keymap = false;
+        let mut key_hold = Duration::from_millis(300);
+        let mut audio = AudioOutput::Bell;
+        let mut rewind_frames = 600;
+        let mut state = None;
+        let mut record = None;
+        let mut play = None;
+        let mut debug = false;
+        let mut breakpoints = Vec::new();
+        let mut seed = None;
+        let mut ipf = None;
+        let mut timer_hz = audio::FRAME_RATE;
+        let mut headless = false;
+        let mut limits = Limits::default();
+        let mut input = None;
+        let mut screenshot = None;
+        let mut dump = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next().ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "--platform" => {
+                    let name = value(arg)?;
+                    platform = Some(Platform::from_name(name).ok_or_else(|| format!("unknown platform `{}`", name))?);
+                }
+                "--quirks" => {
+                    let name = value(arg)?;
+                    quirks = Some(Quirks::preset(name).ok_or_else(|| format!("unknown quirks preset `{}`", name))?);
+                }
+                "--quirk" => quirk_overrides.push(value(arg)?.clone()),
+                "--font" => {
+                    let name = value(arg)?;
+                    font = Some(FontSet::from_name(name).ok_or_else(|| format!("unknown font `{}`", name))?);
+                }
+                "--font-address" => {
+                    let addr = value(arg)?;
+                    let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+                        .map_err(|_| format!("--font-address expects a hex address, got `{}`", addr))?;
+                    font_address = Some(addr);
+                }
+                "--memory" => {
+                    let name = value(arg)?;
+                    memory_policy = MemoryPolicy::from_name(name).ok_or_else(|| format!("unknown memory policy `{}`", name))?;
+                }
+                "--on-unknown" => {
+                    let name = value(arg)?;
+                    unknown_opcode_policy = UnknownOpcodePolicy::from_name(name)
+                        .ok_or_else(|| format!("unknown opcode policy `{}`", name))?;
+                }
+                "--stack-depth" => {
+                    let depth: usize = value(arg)?.parse().map_err(|_| "--stack-depth expects a number".to_string())?;
+                    if !(1..=MAX_STACK_DEPTH).contains(&depth) {
+                        return Err(format!("--stack-depth must be between 1 and {}", MAX_STACK_DEPTH));
+                    }
+                    stack_depth = Some(depth);
+                }
+                "--vip-stack" => vip_stack = true,
+                "--rom-db" => rom_db = Some(value(arg)?.clone()),
+                "--keymap" => keymap = load_keymap(value(arg)?)?,
+                "--key" => key_overrides.push(value(arg)?.clone()),
+                "--print-keymap" => print_keymap = true,
+                "--key-hold" => {
+                    let ms = value(arg)?.parse().map_err(|_| "--key-hold expects milliseconds".to_string())?;
+                    key_hold = Duration::from_millis(ms);
+                }
+                "--audio" => audio = AudioOutput::parse(value(arg)?)?,
+                "--rewind" => {
+                    rewind_frames = value(arg)?.parse().map_err(|_| "--rewind expects a number of frames".to_string())?;
+                }
+                "--state" => state = Some(value(arg)?.clone()),
+                "--record" => record = Some(value(arg)?.clone()),
+                "--play" => play = Some(value(arg)?.clone()),
+                "--debug" => debug = true,
+                "--break" => {
+                    let text = value(arg)?;
+                    breakpoints.push(Breakpoint::parse(text).map_err(|err| format!("--break {}: {}", text, err))?);
+                }
+                "--seed" => {
+                    let n = value(arg)?.parse().map_err(|_| "--seed expects a number".to_string())?;
+                    seed = Some(n);
+                }
+                "--ipf" => {
+                    let n = value(arg)?.parse().map_err(|_| "--ipf expects a number".to_string())?;
+                    if n == 0 {
+                        return Err("--ipf must be at least 1".to_string());
+                    }
+                    ipf = Some(n);
+                }
+                "--timer-hz" => {
+                    timer_hz = value(arg)?.parse().map_err(|_| "--timer-hz expects a frequency".to_string())?;
+                    if !(1..=1000).contains(&timer_hz) {
+                        return Err("--timer-hz must be between 1 and 1000".to_string());
+                    }
+                }
+                "--headless" => headless = true,
+                "--frames" => {
+                    let n = value(arg)?.parse().map_err(|_| "--frames expects a number".to_string())?;
+                    limits.frames = Some(n);
+                }
+                "--max-instructions" => {
+                    let n = value(arg)?.parse().map_err(|_| "--max-instructions expects a number".to_string())?;
+                    limits.instructions = Some(n);
+                }
+                "--until-self-jump" => limits.self_jump = true,
+                "--input" => input = Some(value(arg)?.clone()),
+                "--screenshot" => screenshot = Some(value(arg)?.clone()),
+                "--dump" => dump = Some(value(arg)?.clone()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        let mut scratch = Quirks::default();
+        for setting in &quirk_overrides {
+            scratch.apply(setting).map_err(|err| format!("--quirk {}: {}", setting, err))?;
+        }
+        for setting in key_overrides {
+            keymap.apply(&setting).map_err(|err| format!("--key {}: {}", setting, err))?;
+        }
+        if print_keymap {
+            return Ok(Command::PrintKeymap(keymap));
+        }
+
+        if record.is_some() && play.is_some() {
+            return Err("--record and --play cannot be combined".to_string());
+        }
+        if seed.is_some() && play.is_some() {
+            return Err("movies record their seed, --seed cannot be combined with --play".to_string());
+        }
+        if state.is_some() && (record.is_some() || play.is_some()) {
+            return Err("movies start from the ROM, --state cannot be combined with --record or --play".to_string());
+        }
+        if headless && record.is_some() {
+            return Err("--record needs the terminal, script the input of headless runs with --input".to_string());
+        }
+
+        let debug = (debug || !breakpoints.is_empty()).then_some(DebugOptions { paused: debug, breakpoints });
+        if debug.is_some() && (record.is_some() || play.is_some()) {
+            return Err("the debugger cannot be combined with --record or --play".to_string());
+        }
+        if debug.is_some() && headless {
+            return Err("the debugger needs the terminal, --debug and --break cannot be combined with --headless".to_string());
+        }
+
+        if limits.frames.is_none() && limits.instructions.is_none() {
+            limits.frames = Some(600);
+        }
+        let headless = headless.then_some(HeadlessOptions { limits, input, screenshot, dump });
+
+        match rom_path {
+            Some(rom_path) => Ok(Command::Run(Box::new(Options {
+                rom_path,
+                platform,
+                quirks,
+                quirk_overrides,
+                font,
+                font_address,
+                memory_policy,
+                unknown_opcode_policy,
+                stack_depth,
+                vip_stack,
+                rom_db,
+                keymap,
+                key_hold,
+                seed,
+                ipf,
+                timer_hz,
+                audio,
+                rewind_frames,
+                state,
+                record,
+                play,
+                debug,
+                headless,
+            }))),
+            None => Err("no game path specified".to_string()),
+        }
+    }
+}
+
+
+/// `--keymap` takes either a preset name or the path of a keymap config file.
+fn load_keymap(name: &str) -> Result<Keymap, String> {
+    if let Some(keymap) = Keymap::preset(name) {
+        return Ok(keymap);
+    }
+    let config = std::fs::read_to_string(name)
+        .map_err(|err| format!("`{}` is neither a keymap preset ({}) nor a readable file: {}", name, Keymap::PRESETS.join(", "), err))?;
+    Keymap::parse(&config).map_err(|err| format!("{}: {}", name, err))
+}
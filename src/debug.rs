@@ -0,0 +1,240 @@
+//! Debugger of the terminal frontend: hotkeys, command prompt and state panels.
+
+use chip8emu::debugger::{Breakpoint, Debugger, Stop};
+use chip8emu::disasm::{disassemble, Line};
+use chip8emu::Chip8;
+use termkan::input::KeyEvent;
+use crate::term::Hotkey;
+
+
+const DISASM_BEFORE: u16 = 4; // instructions listed before PC, when they can be found
+const DISASM_LINES: usize = 11;
+const MEMORY_ROWS: usize = 4;
+const MEMORY_ROW_BYTES: usize = 8;
+
+pub const HELP: &str = "c continue  s step  n next  f finish  b ADDR [if COND]  d [ADDR]  m [ADDR]";
+
+
+pub struct DebugView {
+    pub debugger: Debugger,
+    prompt: String,      // command being typed while paused
+    message: String,     // why the debugger paused, or the outcome of the last command
+    memory: Option<u16>, // start of the hex view, None to follow I
+}
+
+
+impl DebugView {
+    pub fn new(breakpoints: &[Breakpoint], paused: bool) -> DebugView {
+        let mut debugger = Debugger::new();
+        for breakpoint in breakpoints {
+            debugger.set_breakpoint(*breakpoint);
+        }
+        let mut view = DebugView { debugger, prompt: String::new(), message: String::new(), memory: None };
+        if paused {
+            view.debugger.pause();
+            view.message = HELP.to_string();
+        }
+        view
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debugger.is_paused()
+    }
+
+    /// Handles F9 to F12, ignores the other hotkeys.
+    pub fn hotkey(&mut self, hotkey: Hotkey, chip8: &Chip8) {
+        match hotkey {
+            Hotkey::Continue if !self.is_paused() => {
+                self.debugger.pause();
+                self.message = "paused".to_string();
+            }
+            Hotkey::Continue => self.resume(),
+            Hotkey::StepOver => self.debugger.step_over(chip8),
+            Hotkey::Step => self.debugger.step(),
+            Hotkey::StepOut => self.debugger.step_out(chip8),
+            _ => {}
+        }
+    }
+
+    /// Edits the prompt with the keys typed while paused, and runs it on Enter.
+    pub fn key(&mut self, key: KeyEvent, chip8: &Chip8) {
+        if !self.is_paused() {
+            return;
+        }
+        match key {
+            KeyEvent::Char('\n') => {
+                let command = std::mem::take(&mut self.prompt);
+                self.run(&command, chip8);
+            }
+            KeyEvent::Char(c) if !c.is_control() => self.prompt.push(c),
+            KeyEvent::Backspace => {
+                self.prompt.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs a prompt command, see `HELP`.
+    pub fn run(&mut self, command: &str, chip8: &Chip8) {
+        if let Err(err) = self.try_run(command, chip8) {
+            self.message = err;
+        }
+    }
+
+    fn try_run(&mut self, command: &str, chip8: &Chip8) -> Result<(), String> {
+        let command = command.trim();
+        let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+        let arg = arg.trim();
+        match name {
+            "" => {}
+            "c" => self.resume(),
+            "s" => self.debugger.step(),
+            "n" => self.debugger.step_over(chip8),
+            "f" => self.debugger.step_out(chip8),
+            "b" => {
+                let breakpoint = Breakpoint::parse(arg)?;
+                self.debugger.set_breakpoint(breakpoint);
+                self.message = format!("breakpoint at {}", breakpoint);
+            }
+            "d" if arg.is_empty() => {
+                self.debugger.breakpoints.clear();
+                self.message = "deleted all breakpoints".to_string();
+            }
+            "d" => {
+                let addr = parse_addr(arg)?;
+                if !self.debugger.remove_breakpoint(addr) {
+                    return Err(format!("no breakpoint at 0x{:03X}", addr));
+                }
+                self.message = format!("deleted the breakpoint at 0x{:03X}", addr);
+            }
+            "m" if arg.is_empty() => self.memory = None,
+            "m" => self.memory = Some(parse_addr(arg)?),
+            _ => return Err(format!("unknown command `{}`: {}", name, HELP)),
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) {
+        self.debugger.resume();
+        self.message.clear();
+    }
+
+    /// Runs a frame under the debugger. Errors pause it instead of ending the program.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, ipf: u32) {
+        let message = match self.debugger.run_frame(chip8, ipf) {
+            Ok(None) => return,
+            Ok(Some(Stop::Breakpoint(addr))) => format!("breakpoint at 0x{:03X}", addr),
+            Ok(Some(Stop::Step)) => String::new(),
+            Ok(Some(Stop::Trapped(addr))) => format!("trapped at 0x{:03X}, continue to skip it", addr),
+            Ok(Some(Stop::Halted)) => "halted".to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        self.message = message;
+        chip8.draw_flag = true;
+    }
+
+    /// Text of the panels: registers, stack, disassembly around PC, memory, breakpoints,
+    /// then the message and prompt.
+    pub fn lines(&self, chip8: &Chip8) -> Vec<String> {
+        let mut lines = vec![format!("PC {:04X}  I {:04X}  DT {:02X}  ST {:02X}", chip8.pc, chip8.i, chip8.delay_timer, chip8.sound_timer)];
+        for (row, values) in chip8.v.chunks(4).enumerate() {
+            let registers: Vec<String> = values.iter().enumerate().map(|(k, v)| format!("V{:X} {:02X}", row * 4 + k, v)).collect();
+            lines.push(registers.join("  "));
+        }
+        let stack: Vec<String> = chip8.stack.entries().iter().map(|addr| format!("{:03X}", addr)).collect();
+        lines.push(format!("stack {}/{} {}", stack.len(), chip8.stack.depth(), stack.join(" ")));
+
+        lines.push(String::new());
+        for line in self.listing(chip8) {
+            let marker = if line.addr == chip8.pc { '>' } else { ' ' };
+            let breakpoint = if self.debugger.has_breakpoint(line.addr) { '*' } else { ' ' };
+            let bytes: String = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            lines.push(format!("{}{} {:04X}  {:<8}  {}", marker, breakpoint, line.addr, bytes, line.text));
+        }
+
+        lines.push(String::new());
+        let start = self.memory.unwrap_or(chip8.i) as usize;
+        for row in 0..MEMORY_ROWS {
+            let addr = start + row * MEMORY_ROW_BYTES;
+            let bytes: Vec<String> = (addr..addr + MEMORY_ROW_BYTES)
+                .map(|addr| chip8.memory.get(addr).map_or("  ".to_string(), |byte| format!("{:02X}", byte)))
+                .collect();
+            lines.push(format!("{:04X}  {}", addr, bytes.join(" ")));
+        }
+
+        lines.push(String::new());
+        let breakpoints: Vec<String> = self.debugger.breakpoints.iter().map(|b| b.to_string()).collect();
+        lines.push(format!("breakpoints {}", if breakpoints.is_empty() { "none".to_string() } else { breakpoints.join(", ") }));
+        lines.push(self.message.clone());
+        if self.is_paused() {
+            lines.push(format!("(paused) {}_", self.prompt));
+        } else {
+            lines.push("(running) F9 to pause".to_string());
+        }
+        lines
+    }
+
+    /// Disassembly around PC. Instructions can be 2 or 4 bytes long, so the listing starts
+    /// at PC when no earlier start lines up with it.
+    fn listing(&self, chip8: &Chip8) -> Vec<Line> {
+        for before in (1..=DISASM_BEFORE).rev() {
+            let Some(start) = chip8.pc.checked_sub(before * 2) else {
+                continue;
+            };
+            let lines = disassemble(chip8, start, DISASM_LINES);
+            if lines.iter().any(|line| line.addr == chip8.pc) {
+                return lines;
+            }
+        }
+        disassemble(chip8, chip8.pc, DISASM_LINES)
+    }
+}
+
+
+/// `2A0` or `0x2A0`
+fn parse_addr(text: &str) -> Result<u16, String> {
+    u16::from_str_radix(text.trim_start_matches("0x"), 16).map_err(|_| format!("invalid address `{}`, expected hex", text))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn debug_view_test() {
+        let mut chip8 = Chip8::init();
+        // 6005 (V0 = 5), 2208 (call 0x208), 1204 (loop), then at 0x208: 00EE (return)
+        chip8.memory[0x200..0x20A].copy_from_slice(&[0x60, 0x05, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, 0x00, 0xEE]);
+        let mut view = DebugView::new(&[], true);
+        for c in "b 208 if V0 == 5".chars() {
+            view.key(KeyEvent::Char(c), &chip8);
+        }
+        view.key(KeyEvent::Char('\n'), &chip8);
+        assert_eq!(view.message, "breakpoint at 0x208 if V0 == 0x5");
+
+        view.run("c", &chip8);
+        view.run_frame(&mut chip8, 10);
+        assert_eq!(view.message, "breakpoint at 0x208");
+        let lines = view.lines(&chip8);
+        assert_eq!(lines[0], "PC 0208  I 0000  DT 00  ST 00");
+        assert_eq!(lines[1], "V0 05  V1 00  V2 00  V3 00");
+        assert_eq!(lines[5], "stack 1/12 204");
+        assert!(lines.contains(&">* 0208  00EE      RET".to_string()));
+        assert_eq!(lines.last().unwrap(), "(paused) _");
+
+        view.run("m 200", &chip8);
+        assert!(view.lines(&chip8).contains(&"0200  60 05 22 08 12 04 00 00".to_string()));
+        view.run("d 300", &chip8);
+        assert_eq!(view.message, "no breakpoint at 0x300");
+        view.run("x", &chip8);
+        assert!(view.message.starts_with("unknown command `x`"));
+
+        // errors pause instead of exiting
+        chip8.memory[0x204..0x206].copy_from_slice(&[0x50, 0x01]);
+        view.hotkey(Hotkey::Continue, &chip8);
+        view.run_frame(&mut chip8, 10);
+        assert!(view.is_paused());
+        assert!(view.message.starts_with("error: "));
+    }
+}
@@ -0,0 +1,457 @@
+//! Breakpoints and stepping, for frontends to build a debugger on.
+//!
+//! `Debugger::run_frame` replaces `Chip8::run_frame`: it runs the frame instruction by
+//! instruction and stops on a breakpoint, at the end of a step, or on an error.
+
+use std::fmt;
+use crate::{Chip8, Chip8Error, OpCode};
+
+
+/// Value of the machine a condition looks at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+    Pc,
+    Dt, // delay timer
+    St, // sound timer
+    Sp, // stack depth in use
+}
+
+
+impl Register {
+    pub fn from_name(name: &str) -> Option<Register> {
+        let name = name.to_uppercase();
+        match name.as_str() {
+            "I" => Some(Register::I),
+            "PC" => Some(Register::Pc),
+            "DT" => Some(Register::Dt),
+            "ST" => Some(Register::St),
+            "SP" => Some(Register::Sp),
+            _ => {
+                let x = name.strip_prefix('V').filter(|x| x.len() == 1)?;
+                usize::from_str_radix(x, 16).ok().map(Register::V)
+            }
+        }
+    }
+
+    pub fn value(&self, chip8: &Chip8) -> u32 {
+        match *self {
+            Register::V(x) => chip8.v[x] as u32,
+            Register::I => chip8.i as u32,
+            Register::Pc => chip8.pc as u32,
+            Register::Dt => chip8.delay_timer,
+            Register::St => chip8.sound_timer,
+            Register::Sp => chip8.stack.len() as u32,
+        }
+    }
+}
+
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+            Register::Sp => write!(f, "SP"),
+        }
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+
+impl Comparison {
+    pub const SYMBOLS: [&'static str; 6] = ["==", "!=", "<", "<=", ">", ">="];
+
+    pub fn from_symbol(symbol: &str) -> Option<Comparison> {
+        match symbol {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        Comparison::SYMBOLS[*self as usize]
+    }
+
+    pub fn holds(&self, left: u32, right: u32) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+
+/// `<register> <comparison> <value>`, such as `V3 == 0x2A` or `DT > 0`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u32,
+}
+
+
+impl Condition {
+    /// The value is decimal, or hex with a `0x` prefix.
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let [register, comparison, value] = words[..] else {
+            return Err(format!("expected `<register> <comparison> <value>`, got `{}`", text.trim()));
+        };
+        let register = Register::from_name(register)
+            .ok_or_else(|| format!("unknown register `{}`, expected V0-VF, I, PC, DT, ST or SP", register))?;
+        let comparison = Comparison::from_symbol(comparison)
+            .ok_or_else(|| format!("unknown comparison `{}`, expected one of {}", comparison, Comparison::SYMBOLS.join(" ")))?;
+        let parsed = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        let value = parsed.map_err(|_| format!("invalid value `{}`", value))?;
+        Ok(Condition { register, comparison, value })
+    }
+
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        self.comparison.holds(self.register.value(chip8), self.value)
+    }
+}
+
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} 0x{:X}", self.register, self.comparison.symbol(), self.value)
+    }
+}
+
+
+/// Stops before the instruction at `addr` is run, if the condition holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub condition: Option<Condition>,
+}
+
+
+impl Breakpoint {
+    /// `<hex address>` or `<hex address> if <condition>`, such as `2A0 if V3 == 5`.
+    pub fn parse(text: &str) -> Result<Breakpoint, String> {
+        let (addr, condition) = match text.split_once(" if ") {
+            Some((addr, condition)) => (addr.trim(), Some(Condition::parse(condition)?)),
+            None => (text.trim(), None),
+        };
+        let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("invalid address `{}`, expected hex", addr))?;
+        Ok(Breakpoint { addr, condition })
+    }
+
+    /// true if the breakpoint stops `chip8` before its next instruction
+    pub fn hits(&self, chip8: &Chip8) -> bool {
+        self.addr == chip8.pc && self.condition.is_none_or(|condition| condition.holds(chip8))
+    }
+}
+
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:03X}", self.addr)?;
+        if let Some(condition) = self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Why `Debugger::run_frame` paused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(u16), // address of the breakpoint
+    Step,            // a step, step over or step out is done
+    Trapped(u16),    // on an instruction trapped by UnknownOpcodePolicy::Trap
+    Halted,          // 00FD
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    Step,
+    StepOver { ret: u16, depth: usize }, // until the call returns to `ret`
+    StepOut { depth: usize },            // until the stack is shallower than `depth`
+}
+
+
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>, // sorted by address, at most one per address
+    mode: Mode,
+    resumed: bool, // true until the first instruction after a resume is run
+}
+
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+
+impl Debugger {
+    /// Starts running, without breakpoints.
+    pub fn new() -> Debugger {
+        Debugger { breakpoints: Vec::new(), mode: Mode::Running, resumed: false }
+    }
+
+    /// Adds `breakpoint`, replacing the one at the same address.
+    pub fn set_breakpoint(&mut self, breakpoint: Breakpoint) {
+        match self.breakpoints.binary_search_by_key(&breakpoint.addr, |b| b.addr) {
+            Ok(index) => self.breakpoints[index] = breakpoint,
+            Err(index) => self.breakpoints.insert(index, breakpoint),
+        }
+    }
+
+    /// Removes the breakpoint at `addr`, returns false if there is none.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.addr != addr);
+        self.breakpoints.len() != before
+    }
+
+    pub fn has_breakpoint(&self, addr: u16) -> bool {
+        self.breakpoints.iter().any(|b| b.addr == addr)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    /// Runs until the next breakpoint.
+    pub fn resume(&mut self) {
+        self.start(Mode::Running);
+    }
+
+    /// Runs a single instruction.
+    pub fn step(&mut self) {
+        self.start(Mode::Step);
+    }
+
+    /// Runs a single instruction, or a whole subroutine if it is a call.
+    pub fn step_over(&mut self, chip8: &Chip8) {
+        match chip8.decode(chip8.pc) {
+            Ok((OpCode::CallSubroutine(_), len)) => {
+                self.start(Mode::StepOver { ret: chip8.pc.wrapping_add(len), depth: chip8.stack.len() })
+            }
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until the current subroutine returns, like `resume` outside of subroutines.
+    pub fn step_out(&mut self, chip8: &Chip8) {
+        match chip8.stack.len() {
+            0 => self.resume(),
+            depth => self.start(Mode::StepOut { depth }),
+        }
+    }
+
+    fn start(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.resumed = true;
+    }
+
+    /// Runs up to `cycles` instructions, unless paused, and ticks the timers if the whole
+    /// frame was run. Returns why it paused, if it did.
+    ///
+    /// Resuming on a breakpoint runs its instruction, and resuming on a trapped instruction
+    /// skips it. Errors pause too, with PC on the failing instruction.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, cycles: u32) -> Result<Option<Stop>, Chip8Error> {
+        if self.is_paused() {
+            return Ok(None);
+        }
+        if let (Some(addr), true) = (chip8.trapped, self.resumed) {
+            let (_, len) = chip8.decode(addr)?;
+            chip8.pc = addr.wrapping_add(len);
+            chip8.trapped = None;
+        }
+
+        for _ in 0..cycles {
+            if let Some(stop) = self.check(chip8) {
+                self.pause();
+                return Ok(Some(stop));
+            }
+            self.resumed = false;
+            if let Err(err) = chip8.step() {
+                self.pause();
+                return Err(err);
+            }
+
+            let done = match self.mode {
+                Mode::Step => true,
+                Mode::StepOver { ret, depth } => chip8.pc == ret && chip8.stack.len() == depth,
+                Mode::StepOut { depth } => chip8.stack.len() < depth,
+                Mode::Paused | Mode::Running => false,
+            };
+            if done && chip8.trapped.is_none() {
+                self.pause();
+                return Ok(Some(Stop::Step));
+            }
+        }
+        if let Some(stop) = self.check(chip8) {
+            self.pause();
+            return Ok(Some(stop));
+        }
+        chip8.tick_timers();
+        Ok(None)
+    }
+
+    /// Reason to stop before the next instruction.
+    fn check(&self, chip8: &Chip8) -> Option<Stop> {
+        if let Some(addr) = chip8.trapped {
+            return Some(Stop::Trapped(addr));
+        }
+        if chip8.halted {
+            return Some(Stop::Halted);
+        }
+        if !self.resumed && self.breakpoints.iter().any(|b| b.hits(chip8)) {
+            return Some(Stop::Breakpoint(chip8.pc));
+        }
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UnknownOpcodePolicy;
+
+    // 6001 (V0 = 1), 2208 (call 0x208), 7001 (V0 += 1), 1206 (loop), then at 0x208:
+    // 7101 (V1 += 1), 00EE (return)
+    const ROM: [u8; 12] = [0x60, 0x01, 0x22, 0x08, 0x70, 0x01, 0x12, 0x06, 0x71, 0x01, 0x00, 0xEE];
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x20C].copy_from_slice(&ROM);
+        chip8
+    }
+
+    #[test]
+    pub fn parse_test() {
+        let breakpoint = Breakpoint::parse("2a0 if v3 >= 0x2A").unwrap();
+        let condition = Condition { register: Register::V(3), comparison: Comparison::Ge, value: 42 };
+        assert_eq!(breakpoint, Breakpoint { addr: 0x2A0, condition: Some(condition) });
+        assert_eq!(breakpoint.to_string(), "0x2A0 if V3 >= 0x2A");
+        assert_eq!(Breakpoint::parse("0x200").unwrap(), Breakpoint { addr: 0x200, condition: None });
+
+        assert!(Breakpoint::parse("xyz").is_err());
+        assert!(Breakpoint::parse("200 if V3").is_err());
+        assert!(Breakpoint::parse("200 if VG == 1").unwrap_err().contains("unknown register"));
+        assert!(Breakpoint::parse("200 if V1 =< 1").unwrap_err().contains("unknown comparison"));
+    }
+
+    #[test]
+    pub fn breakpoint_test() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger.set_breakpoint(Breakpoint::parse("204").unwrap());
+        chip8.delay_timer = 5;
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Breakpoint(0x204)));
+        assert_eq!((chip8.v, chip8.delay_timer), ([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 5));
+
+        // nothing runs while paused
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), None);
+        assert_eq!(chip8.pc, 0x204);
+
+        // the loop comes back to 0x206, not to the breakpoint
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), None);
+        assert_eq!(chip8.delay_timer, 4);
+
+        // conditional breakpoint
+        let mut chip8 = machine();
+        debugger.set_breakpoint(Breakpoint::parse("208 if V1 == 2").unwrap());
+        debugger.remove_breakpoint(0x204);
+        assert!(!debugger.has_breakpoint(0x204));
+        chip8.memory[0x206..0x208].copy_from_slice(&[0x12, 0x02]); // loop back to the call
+        assert_eq!(debugger.run_frame(&mut chip8, 100).unwrap(), Some(Stop::Breakpoint(0x208)));
+        assert_eq!(chip8.v[1], 2);
+    }
+
+    #[test]
+    pub fn step_test() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger.pause();
+        debugger.step();
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Step));
+        assert_eq!(chip8.pc, 0x202);
+
+        // over the call
+        debugger.step_over(&chip8);
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Step));
+        assert_eq!((chip8.pc, chip8.v[1]), (0x204, 1));
+
+        // into the call, then out of it
+        let mut chip8 = machine();
+        chip8.pc = 0x202;
+        debugger.step();
+        debugger.run_frame(&mut chip8, 10).unwrap();
+        assert_eq!((chip8.pc, chip8.stack.len()), (0x208, 1));
+        debugger.step_out(&chip8);
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Step));
+        assert_eq!((chip8.pc, chip8.stack.len()), (0x204, 0));
+    }
+
+    #[test]
+    pub fn errors_test() {
+        let mut chip8 = machine();
+        chip8.memory[0x204..0x206].copy_from_slice(&[0x50, 0x01]); // invalid
+        let mut debugger = Debugger::new();
+        assert!(debugger.run_frame(&mut chip8, 10).is_err());
+        assert!(debugger.is_paused());
+        assert_eq!(chip8.pc, 0x204);
+
+        // errors found while executing stop on the instruction too
+        let mut chip8 = machine();
+        chip8.stack.set_depth(1);
+        chip8.memory[0x208..0x20A].copy_from_slice(&[0x22, 0x08]); // calls itself
+        debugger.resume();
+        assert!(matches!(debugger.run_frame(&mut chip8, 10), Err(Chip8Error::StackOverflow { addr: 0x208 })));
+        assert_eq!((chip8.pc, chip8.stack.len()), (0x208, 1));
+
+        // continuing skips a trapped instruction
+        let mut chip8 = machine();
+        chip8.memory[0x204..0x206].copy_from_slice(&[0x50, 0x01]);
+        chip8.unknown_opcode_policy = UnknownOpcodePolicy::Trap;
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Trapped(0x204)));
+        debugger.step();
+        assert_eq!(debugger.run_frame(&mut chip8, 10).unwrap(), Some(Stop::Step));
+        assert_eq!((chip8.pc, chip8.trapped), (0x206, None));
+    }
+}
@@ -0,0 +1,126 @@
+//! Instruction mnemonics, in the style of Cowgod's CHIP-8 reference.
+
+use std::fmt;
+use crate::{Chip8, OpCode};
+
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OpCode::ClearScreen() => write!(f, "CLS"),
+            OpCode::Return() => write!(f, "RET"),
+            OpCode::Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            OpCode::CallSubroutine(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            OpCode::CondEq(x, nn) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            OpCode::CondNEq(x, nn) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            OpCode::CondEqReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            OpCode::SetReg(x, nn) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            OpCode::AddToReg(x, nn) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            OpCode::AssignRegToReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            OpCode::BitwiseOr(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            OpCode::BitwiseAnd(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            OpCode::BitwiseXor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            OpCode::AddRegToReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            OpCode::SubRegToReg(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            OpCode::StoreLSBWithShift(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            OpCode::SubRegFromReg(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            OpCode::StoreMSBWithShift(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            OpCode::CondNEqReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            OpCode::SetI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            OpCode::JumpToV0Plus(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            OpCode::RegRandBitwiseAnd(x, nn) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            OpCode::DrawSprite(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            OpCode::IsKeyPressed(x) => write!(f, "SKP V{:X}", x),
+            OpCode::IsKeyNPressed(x) => write!(f, "SKNP V{:X}", x),
+            OpCode::SetRegToTimer(x) => write!(f, "LD V{:X}, DT", x),
+            OpCode::AwaitKey(x) => write!(f, "LD V{:X}, K", x),
+            OpCode::SetDelayTimer(x) => write!(f, "LD DT, V{:X}", x),
+            OpCode::SetSoundTimer(x) => write!(f, "LD ST, V{:X}", x),
+            OpCode::AddRegToI(x) => write!(f, "ADD I, V{:X}", x),
+            OpCode::SetIToSprite(x) => write!(f, "LD F, V{:X}", x),
+            OpCode::ToDecimal(x) => write!(f, "LD B, V{:X}", x),
+            OpCode::DumpRegs(x) => write!(f, "LD [I], V{:X}", x),
+            OpCode::LoadRegs(x) => write!(f, "LD V{:X}, [I]", x),
+            OpCode::ScrollDown(n) => write!(f, "SCD {}", n),
+            OpCode::ScrollRight() => write!(f, "SCR"),
+            OpCode::ScrollLeft() => write!(f, "SCL"),
+            OpCode::Exit() => write!(f, "EXIT"),
+            OpCode::LowRes() => write!(f, "LOW"),
+            OpCode::HighRes() => write!(f, "HIGH"),
+            OpCode::SetIToBigSprite(x) => write!(f, "LD HF, V{:X}", x),
+            OpCode::SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            OpCode::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            OpCode::ScrollUp(n) => write!(f, "SCU {}", n),
+            OpCode::SaveRange(x, y) => write!(f, "SAVE V{:X}-V{:X}", x, y),
+            OpCode::LoadRange(x, y) => write!(f, "LOAD V{:X}-V{:X}", x, y),
+            OpCode::SetILong(nnnn) => write!(f, "LD I, 0x{:04X}", nnnn),
+            OpCode::SelectPlanes(n) => write!(f, "PLANE {}", n),
+            OpCode::LoadAudioPattern() => write!(f, "AUDIO"),
+            OpCode::SetPitch(x) => write!(f, "PITCH V{:X}", x),
+            OpCode::MachineCall(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            OpCode::Unknown(word) => write!(f, "DW 0x{:04X}", word),
+        }
+    }
+}
+
+
+/// One decoded instruction of a listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>, // 2 bytes, 4 for XO-CHIP `F000 NNNN`
+    pub text: String,
+}
+
+
+/// Disassembles `count` instructions from `addr`, stopping at the end of memory or of the
+/// address space.
+///
+/// CHIP-8 mixes code and data, so the listing is only right if `addr` is the start of an
+/// instruction.
+pub fn disassemble(chip8: &Chip8, addr: u16, count: usize) -> Vec<Line> {
+    let mut lines = Vec::with_capacity(count);
+    let mut addr = addr;
+    for _ in 0..count {
+        let Ok((opcode, len)) = chip8.decode(addr) else {
+            break;
+        };
+        let bytes = (0..len).filter_map(|k| chip8.read_byte(addr as usize + k as usize).ok()).collect();
+        lines.push(Line { addr, bytes, text: opcode.to_string() });
+        match addr.checked_add(len) {
+            Some(next) => addr = next,
+            None => break,
+        }
+    }
+    lines
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Platform;
+
+    #[test]
+    pub fn mnemonic_test() {
+        assert_eq!(OpCode::SetReg(0xA, 0x2A).to_string(), "LD VA, 0x2A");
+        assert_eq!(OpCode::DrawSprite(1, 2, 5).to_string(), "DRW V1, V2, 5");
+        assert_eq!(OpCode::JumpToV0Plus(0x300).to_string(), "JP V0, 0x300");
+        assert_eq!(OpCode::Unknown(0x5AB1).to_string(), "DW 0x5AB1");
+    }
+
+    #[test]
+    pub fn disassemble_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.memory[0x200..0x208].copy_from_slice(&[0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x22, 0x00]);
+        let lines = disassemble(&chip8, 0x200, 3);
+        assert_eq!(lines.iter().map(|line| line.addr).collect::<Vec<_>>(), [0x200, 0x202, 0x206]);
+        assert_eq!(lines[1].bytes, [0xF0, 0x00, 0x12, 0x34]);
+        assert_eq!(lines[1].text, "LD I, 0x1234");
+        assert_eq!(lines[2].text, "CALL 0x200");
+
+        // stops at the end of memory
+        chip8.memory_policy = crate::MemoryPolicy::Error;
+        assert_eq!(disassemble(&chip8, 0xFFFE, 3).len(), 1);
+    }
+}
@@ -1,100 +1,99 @@
-use crate::Chip8;
-use termkan;
-use termkan::{rds::Renderer, math::*, img::Color};
+use crate::{Chip8, Chip8Error};
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+
+/// Default colours (0xRRGGBB) of the four pixel values: background, plane 1, plane 2,
+/// and both planes. Only the first two are used outside of XO-CHIP.
+pub const DEFAULT_PALETTE: [u32; 4] = [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555];
 
 
 impl Chip8 {
-    pub fn buf_clear_screen(&mut self) {
-        for x in 0..64 {
-            for y in 0..32 {
-                self.display_buf[x][y] = false;
-            }
-        }
+    /// Width of the screen in the current resolution.
+    pub fn width(&self) -> usize {
+        if self.hires { 128 } else { 64 }
     }
 
-    pub fn buf_draw_sprite(&mut self, x: u8, y: u8, n: u8) {
-        // sets VF to 1 if something is XOR'd during drawing
-        self.v[0xF] = 0;
-        let x = x as usize % 64;
-        let y = y as usize % 32;
-        let mut mask;
+    /// Height of the screen in the current resolution.
+    pub fn height(&self) -> usize {
+        if self.hires { 64 } else { 32 }
+    }
 
-        'outer: for k in 0..(n as usize) {
-            mask = 1 << 7;
-            for l in 0..8 {
-                if self.memory[self.i as usize + k as usize] & mask == mask { // si le l-ième bit du row actuel est 1:
-                    if self.display_buf[x + l][y + k] == true {
-                        self.v[0xF] = 1;
-                        self.display_buf[x + l][y + k] = false;
-                    }
-                    else {
-                        self.display_buf[x + l][y + k] = true;
-                    }
-                }
-                if x + l >= 64 - 1 { continue 'outer; }
-                mask >>= 1;
+    /// true if the pixel at (x, y) is lit on any bitplane
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display_buf[x][y] != 0
+    }
+
+    /// Clears the selected bitplanes.
+    pub fn buf_clear_screen(&mut self) {
+        let planes = self.planes;
+        for column in self.display_buf.iter_mut() {
+            for pixel in column.iter_mut() {
+                *pixel &= !planes;
             }
-            if y + k >= 32 - 1 { break 'outer; }
         }
     }
-}
 
+    /// Switches between the 64x32 and 128x64 SUPER-CHIP resolutions, clearing the screen.
+    pub fn buf_set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display_buf = [[0; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+    }
 
-pub struct Display {
-    top_left: Vec2,
-}
-
-
-impl Display {
-    pub fn new() -> Display {
-        let rds = Renderer::get();
-        let dim = Renderer::get_size();
-
-        let top_left = Vec2 {
-            x: (dim.x / 2) - 33,
-            y: (dim.y / 2) - 17,
-        };
-
-        let size = Vec2 {
-            x: 66,
-            y: 34,
-        };
-
-        rds.begin_draw();
-        rds.draw_rect(top_left, size, Color::WHITE);
-        rds.end_draw();
-
-        Display {
-            top_left: Vec2 {
-                x: top_left.x + 1,
-                y: top_left.y + 1
+    /// Scrolls the selected bitplanes by (dx, dy) pixels, towards the bottom right for
+    /// positive values. Pixels scrolled in from the edges are blank.
+    pub fn buf_scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let old = self.display_buf;
+        for x in 0..width {
+            for y in 0..height {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    old[from_x as usize][from_y as usize] & planes
+                } else {
+                    0
+                };
+                let pixel = &mut self.display_buf[x as usize][y as usize];
+                *pixel = (*pixel & !planes) | moved;
             }
         }
     }
 
-    pub fn draw_point(&self, vec2: Vec2, color: Color) {
-        let rds = Renderer::get();
-        let coords = Vec2 {
-            x: self.top_left.x + vec2.x,
-            y: self.top_left.y + vec2.y,
-        };
+    /// Draws the sprite at I on every selected bitplane, one sprite after the other in
+    /// memory, clipping it at the edges of the screen or wrapping it around depending on
+    /// the clip quirk.
+    pub fn buf_draw_sprite(&mut self, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
+        // sets VF to 1 if something is XOR'd during drawing
+        self.v[0xF] = 0;
+        let (width, height) = (self.width(), self.height());
+        let x = x as usize % width;
+        let y = y as usize % height;
 
-        rds.draw_point(coords, color);
-    }
+        // SUPER-CHIP DXY0 draws a 16x16 sprite, two bytes per row
+        let (rows, cols) = if n == 0 && self.platform.has_superchip() { (16, 16) } else { (n as usize, 8) };
+        let mut addr = self.i as usize;
 
-    pub fn update(&mut self, buf: &[[bool; 32]; 64]) {
-        let rds = Renderer::get();
-        rds.begin_draw();
-        for x in 0..64 {
-            for y in 0..32 {
-                if buf[x][y] {
-                    self.draw_point(Vec2::new(x as i32, y as i32), Color::WHITE);
-                }
-                else {
-                    self.draw_point(Vec2::new(x as i32, y as i32), Color::BLACK);
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for k in 0..rows {
+                if self.quirks.clip && y + k >= height { break; }
+                let py = (y + k) % height;
+                for l in 0..cols {
+                    if self.quirks.clip && x + l >= width { break; }
+                    let px = (x + l) % width;
+                    let byte = self.read_byte(addr + k * cols / 8 + l / 8)?;
+                    if byte & (0x80 >> (l % 8)) != 0 { // si le l-ième bit du row actuel est 1:
+                        if self.display_buf[px][py] & plane != 0 {
+                            self.v[0xF] = 1;
+                        }
+                        self.display_buf[px][py] ^= plane;
+                    }
                 }
             }
+            addr += rows * cols / 8;
         }
-        rds.end_draw();
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+
+/// Everything that can go wrong while loading or running a ROM.
+#[derive(Debug)]
+pub enum Chip8Error {
+    UnknownOpcode { addr: u16, word: u16 },        // no instruction of the platform is encoded as `word`
+    StackOverflow { addr: u16 },                   // call at `addr` with a full stack
+    StackUnderflow { addr: u16 },                  // return at `addr` with an empty stack
+    MemoryOutOfBounds { addr: usize, size: usize }, // access past the `size` bytes of memory
+    RomTooLarge { size: usize, max: usize },       // ROM does not fit in memory after 0x200
+    FontOverlapsProgram { addr: u16, end: usize },  // font from `addr` to `end` runs into the program at 0x200
+    Io(io::Error),                                 // ROM or save state could not be read
+    InvalidState(&'static str),                    // save state is corrupt or from another version
+}
+
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { addr, word } => write!(f, "unknown opcode {:04X} at {:03X}", word, addr),
+            Chip8Error::StackOverflow { addr } => write!(f, "stack overflow on call at {:03X}", addr),
+            Chip8Error::StackUnderflow { addr } => write!(f, "return with an empty stack at {:03X}", addr),
+            Chip8Error::MemoryOutOfBounds { addr, size } => {
+                write!(f, "memory access at {:X} out of the {} bytes of memory", addr, size)
+            }
+            Chip8Error::RomTooLarge { size, max } => write!(f, "ROM is {} bytes, at most {} fit in memory", size, max),
+            Chip8Error::FontOverlapsProgram { addr, end } => {
+                write!(f, "font at {:03X}-{:03X} overlaps the program at 200", addr, end - 1)
+            }
+            Chip8Error::Io(err) => write!(f, "{}", err),
+            Chip8Error::InvalidState(reason) => write!(f, "invalid save state: {}", reason),
+        }
+    }
+}
+
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+
+impl From<io::Error> for Chip8Error {
+    fn from(err: io::Error) -> Chip8Error {
+        Chip8Error::Io(err)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn display_test() {
+        assert_eq!(Chip8Error::UnknownOpcode { addr: 0x20A, word: 0x5AB1 }.to_string(), "unknown opcode 5AB1 at 20A");
+        assert_eq!(Chip8Error::StackUnderflow { addr: 0x300 }.to_string(), "return with an empty stack at 300");
+
+        let err = Chip8Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert!(err.source().is_some());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{Chip8, Chip8Error};
+use crate::chip8::PROGRAM_START;
+
+
+pub const FONT_START: u16 = 0x50; // default address of the small font, the big font follows it
+pub const SMALL_GLYPH_LEN: u16 = 5;  // 4x5 hex digits, one byte per row
+pub const BIG_GLYPH_LEN: u16 = 10;   // 8x10 hex digits, one byte per row
+
+
+/// Hex digit glyphs of the interpreter a ROM was written for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FontSet {
+    Vip,       // COSMAC VIP CHIP-8 interpreter
+    Eti660,    // ETI-660 computer
+    Dream6800, // DREAM 6800 CHIPOS
+    #[default]
+    Octo,      // Octo, also used by most modern interpreters
+}
+
+
+const VIP_FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const ETI660_FONT: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const DREAM6800_FONT: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const OCTO_FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// SUPER-CHIP 1.1 8x10 font. The original only has the digits 0 to 9, A to F are Octo's.
+const SCHIP_BIG_FONT: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Octo's 8x10 font.
+const OCTO_BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+
+impl FontSet {
+    pub const PRESETS: [&'static str; 4] = ["vip", "eti660", "dream6800", "octo"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FontSet::Vip => "vip",
+            FontSet::Eti660 => "eti660",
+            FontSet::Dream6800 => "dream6800",
+            FontSet::Octo => "octo",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FontSet> {
+        match name.to_lowercase().as_str() {
+            "vip" | "cosmac-vip" => Some(FontSet::Vip),
+            "eti660" | "eti-660" => Some(FontSet::Eti660),
+            "dream6800" | "dream-6800" => Some(FontSet::Dream6800),
+            "octo" => Some(FontSet::Octo),
+            _ => None
+        }
+    }
+
+    /// 4x5 glyphs of the 16 digits.
+    pub fn small(&self) -> &'static [u8; 80] {
+        match self {
+            FontSet::Vip => &VIP_FONT,
+            FontSet::Eti660 => &ETI660_FONT,
+            FontSet::Dream6800 => &DREAM6800_FONT,
+            FontSet::Octo => &OCTO_FONT,
+        }
+    }
+
+    /// 8x10 glyphs of the 16 digits, for FX30.
+    pub fn big(&self) -> &'static [u8; 160] {
+        match self {
+            FontSet::Octo => &OCTO_BIG_FONT,
+            _ => &SCHIP_BIG_FONT,
+        }
+    }
+}
+
+
+impl Chip8 {
+    /// Writes `font` at `addr`, the small font first and the big font right after it, and
+    /// points FX29 and FX30 at them. Both must fit below the program at 0x200.
+    pub fn load_font(&mut self, font: FontSet, addr: u16) -> Result<(), Chip8Error> {
+        let (small, big) = (font.small(), font.big());
+        let end = addr as usize + small.len() + big.len();
+        if end > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: end - 1, size: self.memory.len() });
+        }
+        if end > PROGRAM_START {
+            return Err(Chip8Error::FontOverlapsProgram { addr, end });
+        }
+
+        let start = addr as usize;
+        self.memory[start..start + small.len()].copy_from_slice(small);
+        self.memory[start + small.len()..end].copy_from_slice(big);
+        self.font = font;
+        self.font_location = addr;
+        self.big_font_location = addr + small.len() as u16;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpCode;
+
+    #[test]
+    pub fn load_font_test() {
+        let mut chip8 = Chip8::init();
+        assert_eq!((chip8.font_location, chip8.big_font_location), (0x50, 0xA0));
+        assert_eq!(&chip8.memory[0x50..0x55], &OCTO_FONT[..5]);
+
+        chip8.load_font(FontSet::Vip, 0x000).unwrap();
+        assert_eq!((chip8.font_location, chip8.big_font_location), (0x000, 0x050));
+        assert_eq!(&chip8.memory[0x05..0x0A], &[0x60, 0x20, 0x20, 0x20, 0x70]);
+        assert_eq!(&chip8.memory[0x50..0x5A], &SCHIP_BIG_FONT[..10]);
+
+        assert!(chip8.load_font(FontSet::Vip, 0xF80).is_err());
+
+        // 240 bytes from 0x1C0 would overwrite the program
+        chip8.memory[0x200] = 0x12;
+        let err = chip8.load_font(FontSet::Vip, 0x1C0).unwrap_err();
+        assert!(matches!(err, Chip8Error::FontOverlapsProgram { addr: 0x1C0, end: 0x2B0 }));
+        assert_eq!(chip8.memory[0x200], 0x12);
+        chip8.load_font(FontSet::Vip, 0x110).unwrap();
+    }
+
+    #[test]
+    pub fn font_character_test() {
+        let mut chip8 = Chip8::init();
+        for digit in 0..16 {
+            chip8.v[0] = digit;
+            chip8.execute_opcode(OpCode::SetIToSprite(0)).unwrap();
+            assert_eq!(chip8.i, FONT_START + SMALL_GLYPH_LEN * digit as u16);
+            chip8.execute_opcode(OpCode::SetIToBigSprite(0)).unwrap();
+            assert_eq!(chip8.i, FONT_START + 80 + BIG_GLYPH_LEN * digit as u16);
+        }
+
+        // only the low nibble selects the digit
+        chip8.v[0] = 0x1F;
+        chip8.execute_opcode(OpCode::SetIToSprite(0)).unwrap();
+        assert_eq!(chip8.i, FONT_START + SMALL_GLYPH_LEN * 0xF);
+    }
+}
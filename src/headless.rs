@@ -0,0 +1,202 @@
+//! Running a ROM without a frontend, for scripts and tests.
+
+use std::fmt::Write;
+use crate::{AudioBackend, Chip8, Chip8Error};
+
+
+/// When a headless run stops, besides halting (00FD) and trapping.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub frames: Option<u64>,
+    pub instructions: Option<u64>,
+    pub self_jump: bool, // stop on a jump to itself (`1NNN` at NNN), the usual end of a test ROM
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Frames,
+    Instructions,
+    SelfJump,
+    Halted,
+    Trapped,
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RunSummary {
+    pub reason: StopReason,
+    pub frames: u64,       // frames completed, timers ticked as many times
+    pub instructions: u64, // instructions executed
+}
+
+
+/// Keypad events to replay, in frame order.
+///
+/// Scripts have one `<frame> press|release <hex key>` event per line, `#` starting a
+/// comment. Events take effect at the start of their frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<(u64, u8, bool)>, // frame, key, pressed
+}
+
+
+impl InputScript {
+    pub fn parse(script: &str) -> Result<InputScript, String> {
+        let mut events = Vec::new();
+        for (n, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", n + 1, msg);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [frame, action, key] = fields[..] else {
+                return Err(err("expected `<frame> press|release <hex key>`"));
+            };
+            let frame = frame.parse().map_err(|_| err("invalid frame number"))?;
+            let pressed = match action {
+                "press" => true,
+                "release" => false,
+                _ => return Err(err("expected press or release")),
+            };
+            let key = u8::from_str_radix(key, 16).ok().filter(|&k| k < 16).ok_or_else(|| err("invalid hex key"))?;
+            events.push((frame, key, pressed));
+        }
+        events.sort_by_key(|&(frame, _, _)| frame);
+        Ok(InputScript { events })
+    }
+
+    /// Applies the events of `frame` to the keypad.
+    pub fn apply(&self, frame: u64, chip8: &mut Chip8) {
+        for &(_, key, pressed) in self.events.iter().filter(|(f, _, _)| *f == frame) {
+            chip8.keypad.set(key, pressed);
+        }
+    }
+}
+
+
+/// Runs frames of `ipf` instructions until one of `limits` is reached, or the
+/// interpreter halts or traps.
+pub fn run(chip8: &mut Chip8, ipf: u32, limits: &Limits, script: &InputScript, audio: &mut dyn AudioBackend) -> Result<RunSummary, Chip8Error> {
+    let mut summary = RunSummary { reason: StopReason::Frames, frames: 0, instructions: 0 };
+    let stop = |summary: &mut RunSummary, reason| {
+        summary.reason = reason;
+        Ok(*summary)
+    };
+
+    loop {
+        if limits.frames.is_some_and(|frames| summary.frames >= frames) {
+            return stop(&mut summary, StopReason::Frames);
+        }
+        script.apply(summary.frames, chip8);
+        audio.frame(chip8.tone())?;
+
+        for _ in 0..ipf {
+            if chip8.halted {
+                return stop(&mut summary, StopReason::Halted);
+            }
+            if chip8.trapped.is_some() {
+                return stop(&mut summary, StopReason::Trapped);
+            }
+            if limits.instructions.is_some_and(|n| summary.instructions >= n) {
+                return stop(&mut summary, StopReason::Instructions);
+            }
+            if limits.self_jump && chip8.is_self_jump() {
+                return stop(&mut summary, StopReason::SelfJump);
+            }
+            chip8.step()?;
+            summary.instructions += 1;
+        }
+        chip8.tick_timers();
+        summary.frames += 1;
+    }
+}
+
+
+impl Chip8 {
+    /// true if the instruction at PC is a jump to itself
+    pub fn is_self_jump(&self) -> bool {
+        let hi = self.read_byte(self.pc as usize).unwrap_or(0);
+        let lo = self.read_byte(self.pc as usize + 1).unwrap_or(0);
+        let word = ((hi as u16) << 8) | lo as u16;
+        word >> 12 == 0x1 && word & 0xFFF == self.pc
+    }
+}
+
+
+/// Registers, timers, stack and a hex dump of the non-zero lines of memory.
+pub fn dump(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    for (k, v) in chip8.v.iter().enumerate() {
+        let sep = if k % 8 == 7 { '\n' } else { ' ' };
+        let _ = write!(out, "V{:X}={:02X}{}", k, v, sep);
+    }
+    let _ = writeln!(out, "I={:04X} PC={:04X} DT={:02X} ST={:02X}", chip8.i, chip8.pc, chip8.delay_timer, chip8.sound_timer);
+    let stack: Vec<String> = chip8.stack.entries().iter().map(|addr| format!("{:04X}", addr)).collect();
+    let _ = writeln!(out, "stack=[{}]", stack.join(" "));
+
+    let _ = writeln!(out, "memory:");
+    for (line, bytes) in chip8.memory.chunks(16).enumerate() {
+        if bytes.iter().all(|&b| b == 0) {
+            continue;
+        }
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let _ = writeln!(out, "{:04X}: {}", line * 16, hex.join(" "));
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NullAudio;
+
+    #[test]
+    pub fn input_script_test() {
+        let script = InputScript::parse("# start\n10 press 5\n\n12 release 5 # done\n").unwrap();
+        let mut chip8 = Chip8::init();
+        script.apply(10, &mut chip8);
+        assert!(chip8.keypad.is_pressed(5));
+        script.apply(12, &mut chip8);
+        assert!(!chip8.keypad.is_pressed(5));
+
+        assert!(InputScript::parse("10 press").is_err());
+        assert!(InputScript::parse("10 hold 5").is_err());
+        assert!(InputScript::parse("10 press G").is_err());
+    }
+
+    #[test]
+    pub fn run_test() {
+        let mut chip8 = Chip8::init();
+        // 7001 (V0 += 1), 7001, then 1204 (jump to itself)
+        chip8.memory[0x200..0x206].copy_from_slice(&[0x70, 0x01, 0x70, 0x01, 0x12, 0x04]);
+
+        let limits = Limits { frames: Some(10), ..Limits::default() };
+        let summary = run(&mut chip8, 3, &limits, &InputScript::default(), &mut NullAudio).unwrap();
+        assert_eq!(summary, RunSummary { reason: StopReason::Frames, frames: 10, instructions: 30 });
+
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x206].copy_from_slice(&[0x70, 0x01, 0x70, 0x01, 0x12, 0x04]);
+        let limits = Limits { frames: Some(10), self_jump: true, ..Limits::default() };
+        let summary = run(&mut chip8, 3, &limits, &InputScript::default(), &mut NullAudio).unwrap();
+        assert_eq!(summary, RunSummary { reason: StopReason::SelfJump, frames: 0, instructions: 2 });
+        assert_eq!(chip8.v[0], 2);
+
+        let limits = Limits { instructions: Some(5), ..Limits::default() };
+        let summary = run(&mut chip8, 3, &limits, &InputScript::default(), &mut NullAudio).unwrap();
+        assert_eq!(summary, RunSummary { reason: StopReason::Instructions, frames: 1, instructions: 5 });
+    }
+
+    #[test]
+    pub fn dump_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[0xA] = 0x42;
+        let dump = dump(&chip8);
+        assert!(dump.contains("VA=42"));
+        assert!(dump.contains("PC=0200"));
+        assert!(dump.contains("0050: F0 90 90 90 F0"));
+        assert!(!dump.contains("0200:"));
+    }
+}
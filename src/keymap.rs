@@ -0,0 +1,199 @@
+use std::fmt;
+
+
+/// Mapping between the 16 hex keys and the characters typed on a host keyboard.
+///
+/// The presets all place the CHIP-8 keypad on the same physical keys, the 4x4 block
+/// starting at `1` on a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 C        1 2 3 4
+/// 4 5 6 D   ->   Q W E R
+/// 7 8 9 E        A S D F
+/// A 0 B F        Z X C V
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [char; 16], // host character of each hex key, indexed by hex value
+}
+
+
+// hex keys in keypad order, row by row
+const KEYPAD_LAYOUT: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+
+impl Keymap {
+    pub const PRESETS: [&'static str; 3] = ["qwerty", "azerty", "dvorak"];
+
+    /// Builds a keymap from the host characters of the keypad, row by row.
+    fn from_rows(rows: &str) -> Keymap {
+        let mut keys = [' '; 16];
+        for (k, c) in rows.chars().filter(|c| !c.is_whitespace()).enumerate() {
+            keys[KEYPAD_LAYOUT[k] as usize] = c;
+        }
+        Keymap { keys }
+    }
+
+    pub fn qwerty() -> Keymap {
+        Keymap::from_rows("1234 qwer asdf zxcv")
+    }
+
+    pub fn azerty() -> Keymap {
+        Keymap::from_rows("&é\"' azer qsdf wxcv")
+    }
+
+    pub fn dvorak() -> Keymap {
+        Keymap::from_rows("1234 ',.p aoeu ;qjk")
+    }
+
+    pub fn preset(name: &str) -> Option<Keymap> {
+        match name.to_lowercase().as_str() {
+            "qwerty" => Some(Keymap::qwerty()),
+            "azerty" => Some(Keymap::azerty()),
+            "dvorak" => Some(Keymap::dvorak()),
+            _ => None
+        }
+    }
+
+    /// Host character bound to the hex key `code`.
+    pub fn char_for(&self, code: u8) -> char {
+        self.keys[(code & 0xF) as usize]
+    }
+
+    /// Hex key bound to the host character `c`, if any. Letters match in both cases.
+    pub fn code_for(&self, c: char) -> Option<u8> {
+        self.keys.iter().position(|&k| k == c)
+            .or_else(|| self.keys.iter().position(|k| k.to_lowercase().eq(c.to_lowercase())))
+            .map(|code| code as u8)
+    }
+
+    /// Binds the hex key `code` to `c`. A hex key previously bound to `c` gets the old
+    /// character of `code`, so that every character maps to a single hex key.
+    pub fn remap(&mut self, code: u8, c: char) {
+        let code = (code & 0xF) as usize;
+        if let Some(other) = self.keys.iter().position(|&k| k == c) {
+            self.keys[other] = self.keys[code];
+        }
+        self.keys[code] = c;
+    }
+
+    /// Parses a keymap configuration.
+    ///
+    /// Each non-empty line is either `preset = <name>`, which resets the whole keymap,
+    /// or `<hex key> = <char>` to remap a single key. `#` starts a comment, and `space`
+    /// and `hash` stand for ` ` and `#`.
+    ///
+    /// ```text
+    /// preset = qwerty
+    /// 5 = k   # use K instead of W for up
+    /// ```
+    pub fn parse(config: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        for (n, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            keymap.apply(line).map_err(|err| format!("line {}: {}", n + 1, err))?;
+        }
+        Ok(keymap)
+    }
+
+    /// Applies a single `preset = <name>` or `<hex key> = <char>` setting.
+    pub fn apply(&mut self, setting: &str) -> Result<(), String> {
+        let (name, value) = setting.split_once('=')
+            .ok_or_else(|| format!("expected `<key> = <value>`, got `{}`", setting))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        if name.eq_ignore_ascii_case("preset") {
+            *self = Keymap::preset(value).ok_or_else(|| format!("unknown keymap preset `{}`", value))?;
+            return Ok(());
+        }
+
+        let code = u8::from_str_radix(name, 16).ok().filter(|&code| code <= 0xF)
+            .ok_or_else(|| format!("`{}` is not a hex key (0-F)", name))?;
+        let c = match value {
+            "space" => ' ',
+            "hash" => '#',
+            _ => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(format!("`{}` is not a single character", value)),
+                }
+            }
+        };
+        self.remap(code, c);
+        Ok(())
+    }
+}
+
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::azerty()
+    }
+}
+
+
+/// Displays the keymap as the 4x4 keypad grid, next to the host keys.
+impl fmt::Display for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in KEYPAD_LAYOUT.chunks(4) {
+            for code in row {
+                write!(f, "{:X} ", code)?;
+            }
+            write!(f, "  ")?;
+            for &code in row {
+                write!(f, " {}", self.char_for(code))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn presets_test() {
+        for name in Keymap::PRESETS {
+            let keymap = Keymap::preset(name).unwrap();
+            for code in 0..16 {
+                assert_eq!(keymap.code_for(keymap.char_for(code)), Some(code), "{}", name);
+            }
+        }
+
+        let qwerty = Keymap::qwerty();
+        assert_eq!(qwerty.code_for('q'), Some(0x4));
+        assert_eq!(qwerty.code_for('Q'), Some(0x4));
+        assert_eq!(qwerty.char_for(0xF), 'v');
+        assert_eq!(Keymap::azerty().code_for('é'), Some(0x2));
+        assert_eq!(Keymap::dvorak().code_for(','), Some(0x5));
+    }
+
+    #[test]
+    pub fn parse_test() {
+        let keymap = Keymap::parse("# comment\npreset = qwerty\n5 = k\nf=space # remap F\n").unwrap();
+        assert_eq!(keymap.code_for('k'), Some(0x5));
+        assert_eq!(keymap.code_for('w'), None);
+        assert_eq!(keymap.code_for(' '), Some(0xF));
+
+        // binding an already used character swaps the two keys
+        let keymap = Keymap::parse("preset = qwerty\n1 = 2").unwrap();
+        assert_eq!(keymap.code_for('2'), Some(0x1));
+        assert_eq!(keymap.code_for('1'), Some(0x2));
+
+        assert!(Keymap::parse("preset = colemak").is_err());
+        assert!(Keymap::parse("G = a").is_err());
+        assert!(Keymap::parse("1 = ab").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+
+/// State of the 16-key hexadecimal keypad (`0x0` to `0xF`).
+///
+/// The interpreter only ever reads this; frontends, tests and scripts translate their
+/// own events into `press`/`release` calls.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keypad {
+    keys: [bool; 16],
+}
+
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad { keys: [false; 16] }
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.set(key, true);
+    }
+
+    pub fn release(&mut self, key: u8) {
+        self.set(key, false);
+    }
+
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        self.keys[(key & 0xF) as usize] = pressed;
+    }
+
+    pub fn release_all(&mut self) {
+        self.keys = [false; 16];
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+
+    /// Returns the lowest hex key currently pressed, if any.
+    pub fn any_pressed(&self) -> Option<u8> {
+        self.keys.iter().position(|&k| k).map(|k| k as u8)
+    }
+
+    /// Pressed state of every key, indexed by hex value.
+    pub fn keys(&self) -> [bool; 16] {
+        self.keys
+    }
+}
+
+
+/// Held-key tracking for event sources that report presses but not (always) releases.
+///
+/// Terminals usually only send a key press, followed by auto-repeat presses while the
+/// key stays down. Each key is therefore kept held for `hold` after its last press or
+/// repeat. Once the source is known to report real releases (e.g. the kitty keyboard
+/// protocol), keys are held until released instead.
+#[derive(Clone, Debug)]
+pub struct KeyHold {
+    pub hold: Duration,
+    last_press: [Option<Instant>; 16],
+    release_events: bool,
+}
+
+
+impl KeyHold {
+    pub fn new(hold: Duration) -> KeyHold {
+        KeyHold {
+            hold,
+            last_press: [None; 16],
+            release_events: false,
+        }
+    }
+
+    /// Registers a press or auto-repeat of `key` at `now`.
+    pub fn press(&mut self, key: u8, now: Instant) {
+        self.last_press[(key & 0xF) as usize] = Some(now);
+    }
+
+    pub fn release(&mut self, key: u8) {
+        self.last_press[(key & 0xF) as usize] = None;
+    }
+
+    /// Tells whether the event source reports key releases.
+    pub fn set_release_events(&mut self, supported: bool) {
+        self.release_events = supported;
+    }
+
+    pub fn release_events(&self) -> bool {
+        self.release_events
+    }
+
+    /// Writes the held state of every key at `now` into `keypad`.
+    pub fn apply(&mut self, keypad: &mut Keypad, now: Instant) {
+        for key in 0..16 {
+            if let Some(pressed_at) = self.last_press[key] {
+                if !self.release_events && now.saturating_duration_since(pressed_at) > self.hold {
+                    self.last_press[key] = None;
+                }
+            }
+            keypad.set(key as u8, self.last_press[key].is_some());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn press_release_test() {
+        let mut keypad = Keypad::new();
+        assert_eq!(keypad.any_pressed(), None);
+
+        keypad.press(0xA);
+        keypad.press(0x3);
+        assert!(keypad.is_pressed(0xA));
+        assert!(!keypad.is_pressed(0xB));
+        assert_eq!(keypad.any_pressed(), Some(0x3));
+
+        keypad.release(0x3);
+        assert_eq!(keypad.any_pressed(), Some(0xA));
+
+        keypad.release_all();
+        assert_eq!(keypad.any_pressed(), None);
+    }
+
+    #[test]
+    pub fn key_hold_test() {
+        let mut keypad = Keypad::new();
+        let mut hold = KeyHold::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        hold.press(0x1, start);
+        hold.press(0x2, start + Duration::from_millis(50));
+        hold.apply(&mut keypad, start + Duration::from_millis(80));
+        assert!(keypad.is_pressed(0x1) && keypad.is_pressed(0x2));
+
+        // 0x1 expires, 0x2 was pressed later and is still held
+        hold.apply(&mut keypad, start + Duration::from_millis(120));
+        assert!(!keypad.is_pressed(0x1));
+        assert!(keypad.is_pressed(0x2));
+
+        // repeats extend the hold
+        hold.press(0x2, start + Duration::from_millis(140));
+        hold.apply(&mut keypad, start + Duration::from_millis(200));
+        assert!(keypad.is_pressed(0x2));
+    }
+
+    #[test]
+    pub fn key_hold_release_events_test() {
+        let mut keypad = Keypad::new();
+        let mut hold = KeyHold::new(Duration::from_millis(100));
+        let start = Instant::now();
+        hold.set_release_events(true);
+
+        hold.press(0x5, start);
+        hold.apply(&mut keypad, start + Duration::from_secs(10));
+        assert!(keypad.is_pressed(0x5));
+
+        hold.release(0x5);
+        hold.apply(&mut keypad, start + Duration::from_secs(10));
+        assert!(!keypad.is_pressed(0x5));
+    }
+}
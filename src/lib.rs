@@ -0,0 +1,45 @@
+//! CHIP-8 interpreter core.
+//!
+//! Everything in this crate is frontend-agnostic: it never touches the terminal,
+//! stdout or a window. Frontends (such as the terminal program in `main.rs`) load a
+//! ROM into a `Chip8`, translate their input events into its `Keypad`, run its opcodes and render `display_buf`.
+
+pub mod audio;
+pub mod chip8;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod font;
+pub mod headless;
+mod json;
+pub mod keymap;
+pub mod keypad;
+pub mod memory;
+pub mod movie;
+pub mod opcodes;
+pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod romdb;
+pub mod savestate;
+pub mod scheduler;
+pub mod screenshot;
+pub mod stack;
+
+pub use audio::AudioBackend;
+pub use chip8::{Chip8, KeyWait};
+pub use error::Chip8Error;
+pub use font::FontSet;
+pub use keymap::Keymap;
+pub use keypad::Keypad;
+pub use memory::MemoryPolicy;
+pub use movie::Movie;
+pub use opcodes::{OpCode, UnknownOpcodePolicy};
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use rewind::Rewind;
+pub use romdb::{RomDatabase, RomInfo};
+pub use scheduler::FrameScheduler;
+pub use stack::Stack;
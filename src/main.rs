@@ -1,95 +1,287 @@
-mod display;
-mod opcodes;
-mod chip8;
-mod input;
-use display::*;
-use chip8::*;
-use input::*;
-use std::time::Duration;
-use std::thread::sleep;
-use std::env;
-
-const OP_PER_SECOND: u64 = 700;
-const OP_TRIGGER_VAL: u64 = 1_0000 / OP_PER_SECOND;
-const TIMER_TRIGGER_VAL: u64 = 1_0000 / 60;
+mod cli;
+mod debug;
+mod session;
+mod slots;
+mod speed;
+mod term;
+use chip8emu::{headless, screenshot, Chip8, Chip8Error, FrameScheduler, Movie, Rewind};
+use chip8emu::audio::{AudioBackend, NullAudio};
+use chip8emu::display::DEFAULT_PALETTE;
+use cli::{AudioOutput, HeadlessOptions, Options};
+use debug::DebugView;
+use session::Session;
+use slots::SaveSlots;
+use speed::Speed;
+use term::*;
+use std::io::Write;
+use std::time::Instant;
+
+
+const CYCLES_PER_FRAME: u32 = 12; // about 700 instructions per second, unless the ROM database gives a tick rate
 
 
 fn main() {
 
-    // Game path: CLI args
-    let args: Vec<String> = env::args().collect();
-    if args.len() <= 1 {
-        eprintln!("Error: no game path specified");
-        std::process::exit(1);
-    }
+    let options = Options::from_args();
 
     // Chip8
+    let rom_db = options.rom_database().unwrap_or_else(|err| {
+        eprintln!("Error loading ROM database: {}", err);
+        std::process::exit(1);
+    });
     let mut chip8 = Chip8::init();
-    chip8.load_data(&args[1]).unwrap();
-    
-    // Graphics
-    let mut display = Display::new();
-    display.update(&chip8.display_buf);
+    let rom_info = chip8.load_data(&options.rom_path, &rom_db, options.platform).unwrap_or_else(|err| {
+        eprintln!("Error loading {}: {}", options.rom_path, err);
+        std::process::exit(1);
+    });
+    options.configure(&mut chip8).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    });
+    if let Some(path) = &options.state {
+        let result = std::fs::read(path).map_err(Chip8Error::from).and_then(|data| chip8.load_state(&data));
+        result.unwrap_or_else(|err| {
+            eprintln!("Error loading {}: {}", path, err);
+            std::process::exit(1);
+        });
+    }
 
-    let mut op_trigger = OP_TRIGGER_VAL;
-    let mut timer_trigger = TIMER_TRIGGER_VAL;
-    
-    let mut input_handler = InputHandler::new();
+    let mut ipf = options.ipf
+        .or_else(|| rom_info.as_ref().and_then(|info| info.tickrate))
+        .unwrap_or(CYCLES_PER_FRAME);
 
-    // idée pour l'input:
-    // chaque opcode, on teste pour récupérer un input.
-    // s'il y avait un input en réserve, pas de pb.
-    // s'il n'y en avait pas, alors on commence à decrease un timer d'une durée de 20 opcodes, à part si on tombe sur un nouvel input entre temps, pendant lesquels on va conserver le dernier input récupéré.
+    let mut session = start_session(&options, &mut chip8, ipf).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    });
+    if let Session::Play { movie, .. } = &session {
+        ipf = movie.ipf;
+    }
 
+    if let Some(headless) = &options.headless {
+        let palette = rom_info.as_ref().and_then(|info| info.palette).unwrap_or(DEFAULT_PALETTE);
+        let movie = match &session {
+            Session::Play { movie, .. } => Some(movie),
+            _ => None,
+        };
+        if let Err(err) = run_headless(&options, headless, &mut chip8, ipf, movie, &palette) {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let mut speed = Speed::new(ipf);
+    let mut slots = SaveSlots::new(&options.rom_path);
+    let mut rewind = Rewind::new(options.rewind_frames);
+    rewind.record(&chip8);
+    let mut notice = String::new(); // how the last movie playback ended
+    let mut debug = options.debug.as_ref().map(|debug| DebugView::new(&debug.breakpoints, debug.paused));
+
+    // Sound
+    let mut audio = options.audio.open(options.timer_hz).unwrap_or_else(|err| {
+        eprintln!("Error opening audio output: {}", err);
+        std::process::exit(1);
+    });
+
+    // Graphics
+    let mut display = Display::new(chip8.platform, debug.is_some());
+    if let Some(palette) = rom_info.as_ref().and_then(|info| info.palette) {
+        display.set_palette(palette);
+    }
+    display.update(&chip8);
+
+    let mut input_handler = InputHandler::new(options.keymap, options.key_hold);
+    if let Some(info) = &rom_info {
+        input_handler.set_key_hints(&info.keys);
+    }
 
-    // Main loop
-    'main: loop {
 
-        if op_trigger == 0 {
-            op_trigger = OP_TRIGGER_VAL;
+    // Main loop, one iteration per due frame
+    let mut scheduler = FrameScheduler::new(options.timer_hz, Instant::now());
+    'main: loop {
 
-            input_handler.update();
+        for _ in 0..scheduler.wait() {
+            input_handler.update(&mut chip8.keypad);
 
-            if input_handler.should_quit() {
+            // the debugger stops on 00FD instead
+            if input_handler.should_quit() || (chip8.halted && debug.is_none()) {
                 break 'main;
             }
 
-            // Process opcode
-            let opcode = chip8.fetch_opcode().unwrap(); // fetch_opcode().unwrap() panics if invalid operation is read in memory (i.e if None is returned)
-            chip8.execute_opcode(opcode, &input_handler).unwrap_or_else(|err| {
-                eprintln!("Error executing opcode: {}", err);
-                std::process::exit(1);
-            });
-
-            // Draw if necessary
-            if chip8.draw_flag {
-                display.update(&chip8.display_buf);
-                chip8.draw_flag = false;
+            for hotkey in input_handler.hotkeys() {
+                match hotkey {
+                    Hotkey::Pause => speed.toggle_pause(),
+                    Hotkey::Advance => speed.advance(),
+                    Hotkey::Slower => speed.slower(),
+                    Hotkey::Faster => speed.faster(),
+                    Hotkey::Turbo => speed.toggle_turbo(),
+                    Hotkey::Save => slots.save(&chip8),
+                    Hotkey::Load if !session.allows_loading() => slots.set_message("load disabled in movies"),
+                    Hotkey::Load => slots.load(&mut chip8),
+                    Hotkey::Slot => slots.next(),
+                    Hotkey::Continue | Hotkey::StepOver | Hotkey::Step | Hotkey::StepOut => {
+                        if let Some(debug) = &mut debug {
+                            debug.hotkey(hotkey, &chip8);
+                        }
+                    }
+                }
             }
-        }
-
+            if let Some(debug) = &mut debug {
+                for &key in &input_handler.presses {
+                    debug.key(key, &chip8);
+                }
+            }
+            let debug_paused = debug.as_ref().is_some_and(DebugView::is_paused);
 
-        // Timers
-        if timer_trigger == 0 { // should decrease the timers this loop
-            timer_trigger = TIMER_TRIGGER_VAL;
-            
-            if chip8.delay_timer > 0 {
-                chip8.delay_timer -= 1;
+            // Runs backwards while the rewind key is held, one recorded frame per frame
+            // Backspace edits the debugger prompt while paused
+            if input_handler.is_rewinding() && session.allows_time_travel() && !debug_paused {
+                if rewind.step_back(&mut chip8) {
+                    session.rewound();
+                }
+                continue;
             }
-            if chip8.sound_timer > 0 {
-                chip8.sound_timer -= 1;
-                if chip8.sound_timer == 0 {
-                    chip8.beep_sound();
+
+            for _ in 0..speed.frames_to_run() {
+                if debug.as_ref().is_some_and(DebugView::is_paused) {
+                    break;
+                }
+                audio.frame(chip8.tone()).unwrap_or_else(|err| {
+                    eprintln!("Error playing sound: {}", err);
+                    std::process::exit(1);
+                });
+
+                // Does nothing while stopped on a trapping opcode. The debugger pauses
+                // on errors instead of failing.
+                let ipf = session.before_frame(&mut chip8, speed.ipf);
+                let result = match &mut debug {
+                    Some(debug) => {
+                        debug.run_frame(&mut chip8, ipf);
+                        Ok(())
+                    }
+                    None => chip8.run_frame(ipf),
+                };
+                if let Err(err) = result {
+                    // keep the movie, to reproduce the error
+                    let _ = session.finish();
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+                rewind.record(&chip8);
+                if let Some(message) = session.after_frame(&chip8, ipf) {
+                    notice = format!("  {}", message);
                 }
             }
         }
 
-        // println!("{:?}", opcode);
-        // End loop
-        timer_trigger -= 1;
-        op_trigger -= 1;
-        sleep(Duration::from_micros(1));
-        // sleep(Duration::from_millis(500));
+        let rewinding = if input_handler.is_rewinding() { "  rewind" } else { "" };
+        display.set_status(&format!("{}  {}{}{}{}", speed.status(options.timer_hz), slots.status(), session.status(), notice, rewinding));
+
+        if let Some(debug) = &debug {
+            display.set_panel(&debug.lines(&chip8));
+        }
+
+        display.set_key_wait(chip8.is_waiting_for_key());
+        display.set_trapped(chip8.trapped.is_some());
+
+        // Draw if necessary
+        if chip8.draw_flag {
+            display.update(&chip8);
+            chip8.draw_flag = false;
+        }
+    }
+
+    drop(audio);
+    drop(input_handler);
+    // before closing the display, which ends the process
+    if let Err(err) = session.finish() {
+        eprintln!("Error writing the movie: {}", err);
+    }
+    display.close();
+}
+
+
+/// Starts recording or loads the movie to play, setting up `chip8` for it.
+fn start_session(options: &Options, chip8: &mut Chip8, ipf: u32) -> Result<Session, String> {
+    if options.record.is_none() && options.play.is_none() {
+        return Ok(Session::Live);
+    }
+    let rom = std::fs::read(&options.rom_path).map_err(|err| format!("{}: {}", options.rom_path, err))?;
+
+    if let Some(path) = &options.record {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        chip8.seed_rng(seed);
+        let session = Session::Record { movie: Movie::new(chip8, &rom, seed, ipf), path: path.clone() };
+        // fail now rather than after the session
+        session.finish().map_err(|err| format!("{}: {}", path, err))?;
+        return Ok(session);
+    }
+    let path = options.play.as_ref().unwrap();
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let movie = Movie::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+    if !movie.matches_rom(&rom) {
+        return Err(format!("{} was recorded with another ROM", path));
+    }
+    movie.configure(chip8).map_err(|err| format!("{}: {}", path, err))?;
+    if movie.frames.is_empty() {
+        return Ok(Session::Live);
+    }
+    Ok(Session::Play { movie, frame: 0 })
+}
+
+
+/// Runs the ROM without the terminal, then writes the screenshot and dump even if the
+/// interpreter failed.
+/// When given a movie, replays it instead and fails on a desync.
+fn run_headless(options: &Options, headless: &HeadlessOptions, chip8: &mut Chip8, ipf: u32, movie: Option<&Movie>, palette: &[u32; 4]) -> Result<(), String> {
+    let script = match &headless.input {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            headless::InputScript::parse(&text).map_err(|err| format!("{}: {}", path, err))?
+        }
+        None => headless::InputScript::default(),
+    };
+    // only a WAV file makes sense without a terminal
+    let mut audio: Box<dyn AudioBackend> = match options.audio {
+        AudioOutput::Wav(_) => options.audio.open(options.timer_hz).map_err(|err| format!("audio output: {}", err))?,
+        _ => Box::new(NullAudio),
+    };
+
+    // the outcome, itself an error on a desync
+    let result = match movie {
+        Some(movie) => movie.replay(chip8, audio.as_mut()).map(|replay| match replay.desync {
+            Some(frame) => Err(format!("desync at frame {} of the movie", frame)),
+            None => Ok(format!("Replayed {} of {} frames", replay.frames, movie.frames.len())),
+        }),
+        None => headless::run(chip8, ipf, &headless.limits, &script, audio.as_mut()).map(|summary| {
+            Ok(format!("Stopped after {} frames and {} instructions: {:?}", summary.frames, summary.instructions, summary.reason))
+        }),
+    };
+    drop(audio);
+
+    if let Some(path) = &headless.screenshot {
+        let image = match path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()) {
+            _ if path == "-" => screenshot::to_ascii(chip8).into_bytes(),
+            Some(ext) if ext == "pbm" => screenshot::to_pbm(chip8),
+            Some(ext) if ext == "png" => screenshot::to_png(chip8, palette),
+            _ => screenshot::to_ascii(chip8).into_bytes(),
+        };
+        write_output(path, &image)?;
+    }
+    if let Some(path) = &headless.dump {
+        write_output(path, headless::dump(chip8).as_bytes())?;
     }
 
-}
\ No newline at end of file
+    let outcome = result.map_err(|err| err.to_string())??;
+    eprintln!("{}", outcome);
+    Ok(())
+}
+
+
+/// Writes `data` to the file at `path`, or to stdout for `-`.
+fn write_output(path: &str, data: &[u8]) -> Result<(), String> {
+    if path == "-" {
+        return std::io::stdout().write_all(data).map_err(|err| err.to_string());
+    }
+    std::fs::write(path, data).map_err(|err| format!("{}: {}", path, err))
+}
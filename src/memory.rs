@@ -0,0 +1,82 @@
+use crate::{Chip8, Chip8Error};
+
+
+/// What happens when an instruction reads or writes past the end of memory.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemoryPolicy {
+    #[default]
+    Wrap,  // addresses wrap around to 0, like the 12-bit address bus of the COSMAC VIP
+    Error, // the access fails with Chip8Error::MemoryOutOfBounds
+}
+
+
+impl MemoryPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryPolicy::Wrap => "wrap",
+            MemoryPolicy::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MemoryPolicy> {
+        match name.to_lowercase().as_str() {
+            "wrap" => Some(MemoryPolicy::Wrap),
+            "error" => Some(MemoryPolicy::Error),
+            _ => None
+        }
+    }
+}
+
+
+impl Chip8 {
+    /// Index in `memory` of `addr`, according to the memory policy.
+    fn memory_index(&self, addr: usize) -> Result<usize, Chip8Error> {
+        let size = self.memory.len();
+        match self.memory_policy {
+            _ if addr < size => Ok(addr),
+            MemoryPolicy::Wrap => Ok(addr % size),
+            MemoryPolicy::Error => Err(Chip8Error::MemoryOutOfBounds { addr, size }),
+        }
+    }
+
+    /// Byte of memory at `addr`.
+    pub fn read_byte(&self, addr: usize) -> Result<u8, Chip8Error> {
+        Ok(self.memory[self.memory_index(addr)?])
+    }
+
+    /// Stores `value` at `addr`.
+    pub fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let index = self.memory_index(addr)?;
+        self.memory[index] = value;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Platform;
+
+    #[test]
+    pub fn wrap_test() {
+        let mut chip8 = Chip8::init();
+        chip8.write_byte(0x1001, 0xAB).unwrap();
+        assert_eq!(chip8.memory[0x001], 0xAB);
+        assert_eq!(chip8.read_byte(0x2001).unwrap(), 0xAB);
+
+        // XO-CHIP wraps at 64KB
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.write_byte(0x1001, 0xAB).unwrap();
+        assert_eq!(chip8.memory[0x1001], 0xAB);
+    }
+
+    #[test]
+    pub fn error_test() {
+        let mut chip8 = Chip8::init();
+        chip8.memory_policy = MemoryPolicy::Error;
+        assert!(matches!(chip8.read_byte(0x1000), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, size: 0x1000 })));
+        assert!(chip8.write_byte(0x1000, 0).is_err());
+        assert!(chip8.write_byte(0xFFF, 0).is_ok());
+    }
+}
@@ -0,0 +1,391 @@
+//! Recorded sessions that replay exactly: the keypad state of every frame, and what is
+//! needed to make the interpreter deterministic.
+//!
+//! Movies are text files:
+//! ```text
+//! chip8emu movie 1
+//! rom 0dbd0fc4e1a8f0ae7e0ab21ed2e6b7a1e4d1ab52
+//! seed 8815273950321548291
+//! platform chip8
+//! quirks shift=off jump=off vf-reset=on clip=on display-wait=on memory=x+1
+//! stack 12 memory ed0
+//! font octo 50
+//! memory wrap
+//! on-unknown halt
+//! ipf 12
+//! frames
+//! 0000 3c8e41a2
+//! 0020 77d0b913
+//! ipf 20
+//! 0020 77d0b913
+//! ```
+//! Each frame line holds the pressed keys as a bitmask (bit N for key N) and a hash of
+//! the screen after the frame, to detect desyncs. `ipf` lines change the instructions
+//! per frame from there on.
+
+use std::fmt::{self, Write};
+use crate::{AudioBackend, Chip8, Chip8Error, FontSet, MemoryPolicy, Platform, Quirks, UnknownOpcodePolicy};
+use crate::romdb::{parse_hash, sha1};
+use crate::font::FONT_START;
+use crate::stack::MAX_STACK_DEPTH;
+
+
+const HEADER: &str = "chip8emu movie 1";
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub keys: u16, // bit N set when key N is pressed
+    pub ipf: u32,  // instructions run in the frame
+    pub hash: u32, // screen_hash after the frame
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_sha1: [u8; 20],
+    pub seed: u64, // RNG seed
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub stack_depth: usize,
+    pub stack_top: Option<u16>, // `Stack::memory_top`
+    pub font: FontSet,
+    pub font_address: u16,
+    pub memory_policy: MemoryPolicy,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+    pub ipf: u32,  // instructions per frame at the start
+    pub frames: Vec<Frame>,
+}
+
+
+/// Outcome of `Movie::replay`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub frames: usize,         // frames replayed
+    pub desync: Option<usize>, // first frame whose screen differs from the recording
+}
+
+
+impl Movie {
+    /// Starts a recording of `rom` on `chip8`, which must already be configured.
+    pub fn new(chip8: &Chip8, rom: &[u8], seed: u64, ipf: u32) -> Movie {
+        Movie {
+            rom_sha1: sha1(rom),
+            seed,
+            platform: chip8.platform,
+            quirks: chip8.quirks,
+            stack_depth: chip8.stack.depth(),
+            stack_top: chip8.stack.memory_top,
+            font: chip8.font,
+            font_address: chip8.font_location,
+            memory_policy: chip8.memory_policy,
+            unknown_opcode_policy: chip8.unknown_opcode_policy,
+            ipf,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut lines = text.lines().enumerate().map(|(n, line)| (n + 1, line.trim())).filter(|(_, line)| !line.is_empty());
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(format!("not a movie, expected `{}` on the first line", HEADER));
+        }
+
+        let (mut rom_sha1, mut seed, mut platform, mut quirks, mut ipf) = (None, None, None, None, None);
+        let (mut stack, mut font, mut memory_policy, mut unknown_opcode_policy) = (None, None, None, None);
+        for (n, line) in lines.by_ref() {
+            let err = |msg: &str| format!("line {}: {}", n, msg);
+            if line == "frames" {
+                break;
+            }
+            let (key, value) = line.split_once(' ').ok_or_else(|| err("expected `<field> <value>`"))?;
+            match key {
+                "rom" => rom_sha1 = Some(parse_hash(value).ok_or_else(|| err("invalid SHA-1"))?),
+                "seed" => seed = Some(value.parse().map_err(|_| err("invalid seed"))?),
+                "platform" => platform = Some(Platform::from_name(value).ok_or_else(|| err("unknown platform"))?),
+                "quirks" => {
+                    let mut parsed = Quirks::default();
+                    for setting in value.split_whitespace() {
+                        parsed.apply(setting).map_err(|msg| err(&msg))?;
+                    }
+                    quirks = Some(parsed);
+                }
+                "stack" => stack = Some(parse_stack(value).ok_or_else(|| err("expected `stack <depth> [memory <hex top>]`"))?),
+                "font" => font = Some(parse_font(value).ok_or_else(|| err("expected `font <name> <hex address>`"))?),
+                "memory" => memory_policy = Some(MemoryPolicy::from_name(value).ok_or_else(|| err("unknown memory policy"))?),
+                "on-unknown" => {
+                    unknown_opcode_policy = Some(UnknownOpcodePolicy::from_name(value).ok_or_else(|| err("unknown opcode policy"))?);
+                }
+                "ipf" => ipf = Some(value.parse().map_err(|_| err("invalid ipf"))?),
+                _ => return Err(err(&format!("unknown field `{}`", key))),
+            }
+        }
+        let missing = |field: &str| format!("missing `{}` field", field);
+        let rom_sha1 = rom_sha1.ok_or_else(|| missing("rom"))?;
+        let seed = seed.ok_or_else(|| missing("seed"))?;
+        let platform = platform.ok_or_else(|| missing("platform"))?;
+        // movies recorded before these fields were added used the defaults
+        let (stack_depth, stack_top) = stack.unwrap_or((platform.stack_depth(), None));
+        let (font, font_address) = font.unwrap_or((FontSet::default(), FONT_START));
+        let mut movie = Movie {
+            rom_sha1,
+            seed,
+            platform,
+            quirks: quirks.ok_or_else(|| missing("quirks"))?,
+            stack_depth,
+            stack_top,
+            font,
+            font_address,
+            memory_policy: memory_policy.unwrap_or_default(),
+            unknown_opcode_policy: unknown_opcode_policy.unwrap_or_default(),
+            ipf: ipf.ok_or_else(|| missing("ipf"))?,
+            frames: Vec::new(),
+        };
+
+        let mut ipf = movie.ipf;
+        for (n, line) in lines {
+            let err = |msg: &str| format!("line {}: {}", n, msg);
+            if let Some(value) = line.strip_prefix("ipf ") {
+                ipf = value.parse().map_err(|_| err("invalid ipf"))?;
+                continue;
+            }
+            let (keys, hash) = line.split_once(' ').ok_or_else(|| err("expected `<keys> <hash>`"))?;
+            let keys = u16::from_str_radix(keys, 16).map_err(|_| err("invalid keys"))?;
+            let hash = u32::from_str_radix(hash.trim(), 16).map_err(|_| err("invalid hash"))?;
+            movie.frames.push(Frame { keys, ipf, hash });
+        }
+        Ok(movie)
+    }
+
+    /// true if the movie was recorded with this ROM
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        sha1(rom) == self.rom_sha1
+    }
+
+    /// Sets up `chip8`, with the ROM loaded, as it was when the recording started. Fails if
+    /// the recorded font does not fit below the program.
+    pub fn configure(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.set_platform(self.platform);
+        chip8.quirks = self.quirks;
+        chip8.stack.set_depth(self.stack_depth);
+        chip8.stack.memory_top = self.stack_top;
+        chip8.memory_policy = self.memory_policy;
+        chip8.unknown_opcode_policy = self.unknown_opcode_policy;
+        chip8.seed_rng(self.seed);
+        chip8.load_font(self.font, self.font_address)
+    }
+
+    /// Appends the frame `chip8` has just run with `ipf` instructions.
+    pub fn record(&mut self, chip8: &Chip8, ipf: u32) {
+        self.frames.push(Frame { keys: keys_mask(chip8), ipf, hash: screen_hash(chip8) });
+    }
+
+    /// Presses the keys of frame `frame` and returns its instructions per frame.
+    pub fn apply(&self, frame: usize, chip8: &mut Chip8) -> u32 {
+        let recorded = self.frames[frame];
+        for key in 0..16 {
+            chip8.keypad.set(key, recorded.keys & (1 << key) != 0);
+        }
+        recorded.ipf
+    }
+
+    /// true if the screen after frame `frame` is the recorded one
+    pub fn check(&self, frame: usize, chip8: &Chip8) -> bool {
+        self.frames[frame].hash == screen_hash(chip8)
+    }
+
+    /// Replays every frame on `chip8`, set up with `configure`, stopping at the first
+    /// desync or when the interpreter halts.
+    pub fn replay(&self, chip8: &mut Chip8, audio: &mut dyn AudioBackend) -> Result<Replay, Chip8Error> {
+        let mut replay = Replay { frames: 0, desync: None };
+        for frame in 0..self.frames.len() {
+            if chip8.halted {
+                break;
+            }
+            let ipf = self.apply(frame, chip8);
+            audio.frame(chip8.tone())?;
+            chip8.run_frame(ipf)?;
+            replay.frames += 1;
+            if !self.check(frame, chip8) {
+                replay.desync = Some(frame);
+                break;
+            }
+        }
+        Ok(replay)
+    }
+}
+
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        let mut rom = String::new();
+        for byte in self.rom_sha1 {
+            let _ = write!(rom, "{:02x}", byte);
+        }
+        writeln!(f, "rom {}", rom)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "platform {}", self.platform.name())?;
+        writeln!(f, "quirks {}", self.quirks.settings().join(" "))?;
+        write!(f, "stack {}", self.stack_depth)?;
+        if let Some(top) = self.stack_top {
+            write!(f, " memory {:x}", top)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "font {} {:x}", self.font.name(), self.font_address)?;
+        writeln!(f, "memory {}", self.memory_policy.name())?;
+        writeln!(f, "on-unknown {}", self.unknown_opcode_policy.name())?;
+        writeln!(f, "ipf {}", self.ipf)?;
+        writeln!(f, "frames")?;
+        let mut ipf = self.ipf;
+        for frame in &self.frames {
+            if frame.ipf != ipf {
+                ipf = frame.ipf;
+                writeln!(f, "ipf {}", ipf)?;
+            }
+            writeln!(f, "{:04x} {:08x}", frame.keys, frame.hash)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// `<depth>` or `<depth> memory <hex top>`
+fn parse_stack(value: &str) -> Option<(usize, Option<u16>)> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    let depth = words.first()?.parse().ok().filter(|depth| (1..=MAX_STACK_DEPTH).contains(depth))?;
+    match words[1..] {
+        [] => Some((depth, None)),
+        ["memory", top] => Some((depth, Some(u16::from_str_radix(top, 16).ok()?))),
+        _ => None,
+    }
+}
+
+
+/// `<name> <hex address>`
+fn parse_font(value: &str) -> Option<(FontSet, u16)> {
+    let (name, addr) = value.split_once(' ')?;
+    Some((FontSet::from_name(name)?, u16::from_str_radix(addr.trim(), 16).ok()?))
+}
+
+
+/// Pressed keys of the keypad, bit N for key N.
+fn keys_mask(chip8: &Chip8) -> u16 {
+    chip8.keypad.keys().iter().enumerate().fold(0, |mask, (key, &pressed)| mask | (pressed as u16) << key)
+}
+
+
+/// 32-bit FNV-1a hash of the visible screen and its resolution.
+pub fn screen_hash(chip8: &Chip8) -> u32 {
+    let mut hash = 0x811C_9DC5u32;
+    let mut feed = |byte: u8| hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+    feed(chip8.hires as u8);
+    for y in 0..chip8.height() {
+        for x in 0..chip8.width() {
+            feed(chip8.display_buf[x][y]);
+        }
+    }
+    hash
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NullAudio;
+
+    // CXFF (V0 = random byte), F029 (I = glyph of V0), D015 (draw it), E19E (skip if
+    // key V1 is pressed), 1200 (loop), 1200
+    const ROM: [u8; 12] = [0xC0, 0xFF, 0xF0, 0x29, 0xD0, 0x15, 0xE1, 0x9E, 0x12, 0x00, 0x12, 0x00];
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x20C].copy_from_slice(&ROM);
+        chip8.quirks.display_wait = false;
+        chip8
+    }
+
+    #[test]
+    pub fn record_replay_test() {
+        let mut chip8 = machine();
+        let mut movie = Movie::new(&chip8, &ROM, 42, 5);
+        movie.configure(&mut chip8).unwrap();
+        for frame in 0..30 {
+            chip8.keypad.set(0, frame % 3 == 0);
+            let ipf = if frame < 20 { 5 } else { 7 };
+            chip8.run_frame(ipf).unwrap();
+            movie.record(&chip8, ipf);
+        }
+
+        let text = movie.to_string();
+        assert!(text.contains("\nipf 7\n"));
+        let parsed = Movie::parse(&text).unwrap();
+        assert_eq!(parsed, movie);
+        assert!(parsed.matches_rom(&ROM));
+
+        let mut chip8 = machine();
+        parsed.configure(&mut chip8).unwrap();
+        let replay = parsed.replay(&mut chip8, &mut NullAudio).unwrap();
+        assert_eq!(replay, Replay { frames: 30, desync: None });
+
+        // another seed draws other glyphs
+        let mut chip8 = machine();
+        Movie { seed: 43, ..parsed.clone() }.configure(&mut chip8).unwrap();
+        let replay = parsed.replay(&mut chip8, &mut NullAudio).unwrap();
+        assert!(replay.desync.is_some());
+    }
+
+    #[test]
+    pub fn parse_errors_test() {
+        assert!(Movie::parse("hello").is_err());
+        assert!(Movie::parse("chip8emu movie 1\nseed 1\nframes\n").unwrap_err().contains("missing `rom`"));
+        let movie = Movie::new(&Chip8::init(), &ROM, 1, 12).to_string();
+        assert!(Movie::parse(&format!("{}0001 zz\n", movie)).unwrap_err().starts_with("line 12"));
+        assert!(Movie::parse(&movie.replace("stack 12", "stack 0")).unwrap_err().starts_with("line 6"));
+    }
+
+    #[test]
+    pub fn stack_settings_test() {
+        let mut chip8 = machine();
+        chip8.stack.set_depth(4);
+        chip8.stack.memory_top = Some(0xED0);
+        let text = Movie::new(&chip8, &ROM, 1, 12).to_string();
+        assert!(text.contains("\nstack 4 memory ed0\n"));
+
+        // --stack-depth and --vip-stack of the player do not matter
+        let mut player = machine();
+        player.stack.set_depth(16);
+        Movie::parse(&text).unwrap().configure(&mut player).unwrap();
+        assert_eq!((player.stack.depth(), player.stack.memory_top), (4, Some(0xED0)));
+
+        // movies without the field use the platform's stack
+        let movie = Movie::parse(&text.replace("stack 4 memory ed0\n", "")).unwrap();
+        assert_eq!((movie.stack_depth, movie.stack_top), (12, None));
+    }
+
+    #[test]
+    pub fn interpreter_settings_test() {
+        let mut chip8 = machine();
+        chip8.load_font(FontSet::Vip, 0x100).unwrap();
+        chip8.memory_policy = MemoryPolicy::Error;
+        chip8.unknown_opcode_policy = UnknownOpcodePolicy::Trap;
+        let movie = Movie::new(&chip8, &ROM, 1, 12);
+        let text = movie.to_string();
+        assert!(text.contains("\nfont vip 100\nmemory error\non-unknown trap\n"));
+        assert_eq!(Movie::parse(&text).unwrap(), movie);
+
+        // --font, --memory and --on-unknown of the player do not matter
+        let mut player = machine();
+        movie.configure(&mut player).unwrap();
+        assert_eq!((player.font, player.font_location), (FontSet::Vip, 0x100));
+        assert_eq!(&player.memory[0x100..0x150], &chip8.memory[0x100..0x150]);
+        assert_eq!(player.memory_policy, MemoryPolicy::Error);
+        assert_eq!(player.unknown_opcode_policy, UnknownOpcodePolicy::Trap);
+
+        // movies without the fields use the defaults
+        let text = text.replace("font vip 100\nmemory error\non-unknown trap\n", "");
+        let movie = Movie::parse(&text).unwrap();
+        assert_eq!((movie.font, movie.font_address), (FontSet::Octo, FONT_START));
+        assert_eq!((movie.memory_policy, movie.unknown_opcode_policy), (MemoryPolicy::Wrap, UnknownOpcodePolicy::Halt));
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{Chip8, InputHandler};
-use termkan::{input::KeyEvent};
-use rand::Rng;
+use crate::{Chip8, Chip8Error, KeyWait};
+use crate::font::{BIG_GLYPH_LEN, SMALL_GLYPH_LEN};
+use crate::quirks::MemoryIncrement;
 
 
 type N = u8;
 type NN = u8;
+#[allow(clippy::upper_case_acronyms)]
 type NNN = u16;
 type Vx = usize;
 type Vy = usize;
@@ -27,9 +28,9 @@ pub enum OpCode {
     BitwiseXor(Vx, Vy),
     AddRegToReg(Vx, Vy), // with carry
     SubRegToReg(Vx, Vy), // with carry
-    StoreLSBWithShift(Vx),
+    StoreLSBWithShift(Vx, Vy),
     SubRegFromReg(Vx, Vy), // with carry
-    StoreMSBWithShift(Vx),
+    StoreMSBWithShift(Vx, Vy),
     CondNEqReg(Vx, Vy),
     SetI(NNN),
     JumpToV0Plus(NNN),
@@ -46,6 +47,57 @@ pub enum OpCode {
     ToDecimal(Vx),
     DumpRegs(Vx),
     LoadRegs(Vx),
+    // SUPER-CHIP
+    ScrollDown(N),
+    ScrollRight(),
+    ScrollLeft(),
+    Exit(),
+    LowRes(),
+    HighRes(),
+    SetIToBigSprite(Vx),
+    SaveFlags(Vx),
+    LoadFlags(Vx),
+    // XO-CHIP
+    ScrollUp(N),
+    SaveRange(Vx, Vy),
+    LoadRange(Vx, Vy),
+    SetILong(u16), // 4-byte instruction
+    SelectPlanes(N),
+    LoadAudioPattern(),
+    SetPitch(Vx),
+    // not interpretable
+    MachineCall(NNN), // 0NNN, runs a machine code routine of the original hardware
+    Unknown(u16),     // no instruction of the platform is encoded as this word
+}
+
+
+/// What the interpreter does on `OpCode::MachineCall` and `OpCode::Unknown`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOpcodePolicy {
+    #[default]
+    Halt, // fail with Chip8Error::UnknownOpcode
+    Skip, // ignore the instruction
+    Trap, // stop on the instruction and set `trapped`, for a debugger to take over
+}
+
+
+impl UnknownOpcodePolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnknownOpcodePolicy::Halt => "halt",
+            UnknownOpcodePolicy::Skip => "skip",
+            UnknownOpcodePolicy::Trap => "trap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<UnknownOpcodePolicy> {
+        match name.to_lowercase().as_str() {
+            "halt" => Some(UnknownOpcodePolicy::Halt),
+            "skip" => Some(UnknownOpcodePolicy::Skip),
+            "trap" => Some(UnknownOpcodePolicy::Trap),
+            _ => None
+        }
+    }
 }
 
 
@@ -56,7 +108,7 @@ impl Chip8 {
             0 => opcode >> 12,
             1 => (opcode & 0x_0F00) >> 8,
             2 => (opcode & 0x_00F0) >> 4,
-            3 => (opcode & 0x_000F),
+            3 => opcode & 0x_000F,
             _ => 0x0 // should not happen
         }
     }
@@ -65,65 +117,55 @@ impl Chip8 {
     fn range_nibble(start: u8, end: u8, opcode: u16) -> u16{
         let mut mask = 0;
         for i in start..end {
-            mask |= 0xF << 4*(4-i-1);
+            mask |= 0xF << (4 * (4 - i - 1));
         }
-       (opcode & mask) >> (4-end)*4
-    }
-
-
-    pub fn kkey_from_code(code: u8) -> Option<KeyEvent> {
-        match code {
-            0x1 => Some(KeyEvent::Char('&')),
-            0x2 => Some(KeyEvent::Char('é')),
-            0x3 => Some(KeyEvent::Char('"')),
-            0xC => Some(KeyEvent::Char('\'')),
-            0x4 => Some(KeyEvent::Char('a')),
-            0x5 => Some(KeyEvent::Char('z')),
-            0x6 => Some(KeyEvent::Char('e')),
-            0xD => Some(KeyEvent::Char('r')),
-            0x7 => Some(KeyEvent::Char('q')),
-            0x8 => Some(KeyEvent::Char('s')),
-            0x9 => Some(KeyEvent::Char('d')),
-            0xE => Some(KeyEvent::Char('f')),
-            0xA => Some(KeyEvent::Char('w')),
-            0x0 => Some(KeyEvent::Char('x')),
-            0xB => Some(KeyEvent::Char('c')),
-            0xF => Some(KeyEvent::Char('v')),
-            _ => None
+       (opcode & mask) >> ((4 - end) * 4)
+    }
+
+
+    fn read_word(&self, addr: u16) -> Result<u16, Chip8Error> {
+        let part1 = self.read_byte(addr as usize)?;
+        let part2 = self.read_byte(addr as usize + 1)?;
+        Ok(((part1 as u16) << 8) | (part2 as u16))
+    }
+
+
+    /// Skips the next instruction. On XO-CHIP, skipping over `F000 NNNN` skips all 4 bytes.
+    fn skip_next(&mut self) -> Result<(), Chip8Error> {
+        if self.platform.has_xochip() && self.read_word(self.pc)? == 0xF000 {
+            self.pc = self.pc.wrapping_add(2);
         }
+        self.pc = self.pc.wrapping_add(2);
+        Ok(())
     }
 
 
-    pub fn code_from_kkey(key: KeyEvent) -> Option<u8> {
-        match key {
-            KeyEvent::Char('&') =>  Some(0x1),
-            KeyEvent::Char('é') =>  Some(0x2),
-            KeyEvent::Char('"') =>  Some(0x3),
-            KeyEvent::Char('\'') => Some(0xC),
-            KeyEvent::Char('a') =>  Some(0x4),
-            KeyEvent::Char('z') =>  Some(0x5),
-            KeyEvent::Char('e') =>  Some(0x6),
-            KeyEvent::Char('r') =>  Some(0xD),
-            KeyEvent::Char('q') =>  Some(0x7),
-            KeyEvent::Char('s') =>  Some(0x8),
-            KeyEvent::Char('d') =>  Some(0x9),
-            KeyEvent::Char('f') =>  Some(0xE),
-            KeyEvent::Char('w') =>  Some(0xA),
-            KeyEvent::Char('x') =>  Some(0x0),
-            KeyEvent::Char('c') =>  Some(0xB),
-            KeyEvent::Char('v') =>  Some(0xF),
-            _ => None
+    fn increment_i_after_load_store(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::XPlusOne => self.i = self.i.wrapping_add(x as u16 + 1),
+            MemoryIncrement::X => self.i = self.i.wrapping_add(x as u16),
+            MemoryIncrement::None => {}
         }
+    }
+
 
+    /// Registers from Vx to Vy included, counting down if y < x.
+    fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+        (0..=x.abs_diff(y)).map(move |k| if x <= y { x + k } else { x - k })
     }
 
 
-    pub fn fetch_opcode(&mut self) -> Option<OpCode> {
-        let part1 = self.memory[self.pc as usize];
-        let part2 = self.memory[(self.pc + 1 ) as usize];
-        let opcode: u16 = ((part1 as u16) << 8) | (part2 as u16);
+    /// Decodes the instruction at PC and moves PC past it.
+    pub fn fetch_opcode(&mut self) -> Result<OpCode, Chip8Error> {
+        let (opcode, len) = self.decode(self.pc)?;
+        self.pc = self.pc.wrapping_add(len);
+        Ok(opcode)
+    }
+
 
-        self.pc += 2;
+    /// Decodes the instruction at `addr`, and returns it with its length in bytes.
+    pub fn decode(&self, addr: u16) -> Result<(OpCode, u16), Chip8Error> {
+        let opcode = self.read_word(addr)?;
 
         let x = Self::nth_nibble(1, opcode) as usize;
         let y = Self::nth_nibble(2, opcode) as usize;
@@ -131,12 +173,20 @@ impl Chip8 {
         let nn = Self::range_nibble(2, 4, opcode) as u8;
         let nnn = Self::range_nibble(1, 4, opcode);
 
-        match Self::nth_nibble(0, opcode) {
+        let decoded = match Self::nth_nibble(0, opcode) {
             0x0 => {
                 match opcode {
                     0x00E0 => Some(OpCode::ClearScreen()), // 00E0
                     0x00EE => Some(OpCode::Return()), // 00EE
-                    _      => None
+                    _ if !self.platform.has_superchip() => Some(OpCode::MachineCall(nnn)), // 0NNN
+                    0x00C0..=0x00CF => Some(OpCode::ScrollDown(n)), // 00CN
+                    0x00D0..=0x00DF if self.platform.has_xochip() => Some(OpCode::ScrollUp(n)), // 00DN
+                    0x00FB => Some(OpCode::ScrollRight()), // 00FB
+                    0x00FC => Some(OpCode::ScrollLeft()), // 00FC
+                    0x00FD => Some(OpCode::Exit()), // 00FD
+                    0x00FE => Some(OpCode::LowRes()), // 00FE
+                    0x00FF => Some(OpCode::HighRes()), // 00FF
+                    _      => Some(OpCode::MachineCall(nnn)) // 0NNN
                 }
             }
             0x1 => Some(OpCode::Jump(nnn)), // 1nnn
@@ -146,6 +196,8 @@ impl Chip8 {
             0x5 => {
                 match n {
                     0x0 => Some(OpCode::CondEqReg(x, y)), // 5XY0
+                    0x2 if self.platform.has_xochip() => Some(OpCode::SaveRange(x, y)), // 5XY2
+                    0x3 if self.platform.has_xochip() => Some(OpCode::LoadRange(x, y)), // 5XY3
                     _   => None
                 }
             }
@@ -159,9 +211,9 @@ impl Chip8 {
                     3 => Some(OpCode::BitwiseXor(x, y)), // 8XY3
                     4 => Some(OpCode::AddRegToReg(x, y)), // 8XY4
                     5 => Some(OpCode::SubRegToReg(x, y)), // 8XY5
-                    6 => Some(OpCode::StoreLSBWithShift(x)), // 8XY6
+                    6 => Some(OpCode::StoreLSBWithShift(x, y)), // 8XY6
                     7 => Some(OpCode::SubRegFromReg(x, y)), // 8XY7
-                    0xE => Some(OpCode::StoreMSBWithShift(x)), // 8XYE
+                    0xE => Some(OpCode::StoreMSBWithShift(x, y)), // 8XYE
                     _ => None
                 }
             }
@@ -182,6 +234,10 @@ impl Chip8 {
                     _    => None
                 }
             }
+            0xF if self.platform.has_xochip() && opcode == 0xF000 => {
+                let nnnn = self.read_word(addr.wrapping_add(2))?;
+                return Ok((OpCode::SetILong(nnnn), 4)); // F000 NNNN
+            }
             0xF => {
                 match nn {
                     0x07 => Some(OpCode::SetRegToTimer(x)), // FX07
@@ -193,46 +249,71 @@ impl Chip8 {
                     0x33 => Some(OpCode::ToDecimal(x)), // FX33
                     0x55 => Some(OpCode::DumpRegs(x)), // FX55
                     0x65 => Some(OpCode::LoadRegs(x)), // FX6E
+                    _ if !self.platform.has_superchip() => None,
+                    0x30 => Some(OpCode::SetIToBigSprite(x)), // FX30
+                    0x75 => Some(OpCode::SaveFlags(x)), // FX75
+                    0x85 => Some(OpCode::LoadFlags(x)), // FX85
+                    _ if !self.platform.has_xochip() => None,
+                    0x01 => Some(OpCode::SelectPlanes(x as u8)), // FN01
+                    0x02 if x == 0 => Some(OpCode::LoadAudioPattern()), // F002
+                    0x3A => Some(OpCode::SetPitch(x)), // FX3A
                     _    => None
                 }
             }
             _ => None
+        };
+
+        Ok((decoded.unwrap_or(OpCode::Unknown(opcode)), 2))
+    }
+
+
+    /// Applies the unknown opcode policy to `word`, the instruction just fetched.
+    fn unknown_opcode(&mut self, word: u16) -> Result<(), Chip8Error> {
+        let addr = self.pc.wrapping_sub(2);
+        match self.unknown_opcode_policy {
+            UnknownOpcodePolicy::Halt => {
+                self.pc = addr;
+                Err(Chip8Error::UnknownOpcode { addr, word })
+            }
+            UnknownOpcodePolicy::Skip => Ok(()),
+            UnknownOpcodePolicy::Trap => {
+                self.pc = addr;
+                self.trapped = Some(addr);
+                Ok(())
+            }
         }
     }
 
 
-    pub fn execute_opcode(&mut self, opcode: OpCode, input_handler: &InputHandler) -> Result<(), &'static str>{
+    pub fn execute_opcode(&mut self, opcode: OpCode) -> Result<(), Chip8Error> {
         match opcode {
             OpCode::ClearScreen() => {
                 self.buf_clear_screen();
                 self.draw_flag = true;
             },
             OpCode::Return() => {
-                match self.stack.pop() {
-                    Some(elt) => self.pc = elt,
-                    None => return Err("Tried to return from subroutine with empty stack")
-                }
+                self.pc = self.pop_return(self.pc.wrapping_sub(2))?;
             }
             OpCode::Jump(nnn) => {
                 self.pc = nnn
             }
             OpCode::CallSubroutine(nnn) => {
-                self.stack.push(self.pc);
+                self.push_return(self.pc.wrapping_sub(2), self.pc)?;
                 self.pc = nnn;
             }
             OpCode::CondEq(x, nn) => {
                 if self.v[x] == nn {
-                    self.pc += 2
+                    self.skip_next()?;
                 }
             }
             OpCode::CondNEq(x, nn) => {
                 if self.v[x] != nn {
-                    self.pc += 2
+                    self.skip_next()?;
                 }
             }
             OpCode::CondEqReg(x, y) => {
                 if self.v[x] == self.v[y] {
-                    self.pc += 2
+                    self.skip_next()?;
                 }
             }
             OpCode::SetReg(x, nn) => {
@@ -245,13 +326,22 @@ impl Chip8 {
                 self.v[x] = self.v[y];
             }
             OpCode::BitwiseOr(x, y) => {
-                self.v[x] |= self.v[y]
+                self.v[x] |= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
             OpCode::BitwiseAnd(x, y) => {
                 self.v[x] &= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
             OpCode::BitwiseXor(x, y) => {
                 self.v[x] ^= self.v[y];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
             OpCode::AddRegToReg(x, y) => {
                 let (res, carry) = u8::overflowing_add(self.v[x], self.v[y]);
@@ -263,56 +353,77 @@ impl Chip8 {
                 self.v[x] = res;
                 self.v[0xF] = (!carry) as u8; // carry should be 0 when overflow, 1 otherwise
             }
-            OpCode::StoreLSBWithShift(x) => {
-                self.v[0xF] = self.v[x] & 1;
-                self.v[x] >>= 1;
+            OpCode::StoreLSBWithShift(x, y) => {
+                let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 1;
             }
             OpCode::SubRegFromReg(x, y) => {
                 let (res, carry) = u8::overflowing_sub(self.v[y], self.v[x]);
                 self.v[x] = res;
                 self.v[0xF] = (!carry) as u8; // carry should be 0 when overflow, 1 otherwise
             }
-            OpCode::StoreMSBWithShift(x) => {
-                self.v[0xF] = self.v[x] >> 7;
-                self.v[x] <<= 1;
+            OpCode::StoreMSBWithShift(x, y) => {
+                let value = if self.quirks.shift { self.v[x] } else { self.v[y] };
+                self.v[x] = value << 1;
+                self.v[0xF] = value >> 7;
             }
             OpCode::CondNEqReg(x, y) => {
                 if self.v[x] != self.v[y] {
-                    self.pc += 2;
+                    self.skip_next()?;
                 }
             }
             OpCode::SetI(nnn) => {
                 self.i = nnn;
             }
             OpCode::JumpToV0Plus(nnn) => {
-                self.pc = self.v[0] as u16 + nnn;
+                // BXNN on CHIP-48 and SUPER-CHIP
+                let reg = if self.quirks.jump { (nnn >> 8) as usize } else { 0 };
+                self.pc = self.v[reg] as u16 + nnn;
             }
             OpCode::RegRandBitwiseAnd(x, nn) => {
-                let num: u8 = rand::thread_rng().gen_range(0..255);
+                let num = self.rng.next_byte();
                 self.v[x] = num & nn;
             }
             OpCode::DrawSprite(x, y, n) => {
-                self.buf_draw_sprite(self.v[x], self.v[y], n);
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        // wait for the next frame
+                        self.pc = self.pc.wrapping_sub(2);
+                        return Ok(());
+                    }
+                    self.vblank = false;
+                }
+                self.buf_draw_sprite(self.v[x], self.v[y], n)?;
                 self.draw_flag = true;
             }
             OpCode::IsKeyPressed(x) => {
-                if input_handler.is_key_down(Chip8::kkey_from_code(self.v[x]).unwrap()) {
-                    self.pc += 2
+                if self.keypad.is_pressed(self.v[x]) {
+                    self.skip_next()?;
                 }
             }
             OpCode::IsKeyNPressed(x)=> {
-                if input_handler.is_key_up(Chip8::kkey_from_code(self.v[x]).unwrap()) {
-                    self.pc += 2
+                if !self.keypad.is_pressed(self.v[x]) {
+                    self.skip_next()?;
                 }
             }
             OpCode::SetRegToTimer(x) => {
                 self.v[x] = self.delay_timer as u8;
             }
             OpCode::AwaitKey(x) => {
-                match input_handler.any_key_pressed() {
-                    Some(key_as_hex) => self.v[x] = key_as_hex,
-                    None => self.pc -= 2,
+                let mut wait = self.key_wait.unwrap_or(KeyWait { register: x, pressed: None });
+                match wait.pressed {
+                    Some(key) if !self.keypad.is_pressed(key) => {
+                        self.v[x] = key;
+                        self.key_wait = None;
+                        return Ok(());
+                    }
+                    Some(_) => {}
+                    None => wait.pressed = self.keypad.any_pressed(),
                 }
+                // re-run FX0A until the key is released
+                self.key_wait = Some(wait);
+                self.pc = self.pc.wrapping_sub(2);
             }
             OpCode::SetDelayTimer(x) => {
                 self.delay_timer = self.v[x] as u32;
@@ -322,26 +433,94 @@ impl Chip8 {
 
             }
             OpCode::AddRegToI(x) => {
-                self.i += self.v[x] as u16;
+                self.i = self.i.wrapping_add(self.v[x] as u16);
             }
             OpCode::SetIToSprite(x) => {
-                self.i = self.font_location + 5 * ((self.v[x] % 15) as u16); 
+                self.i = self.font_location + SMALL_GLYPH_LEN * (self.v[x] & 0xF) as u16;
             }
             OpCode::ToDecimal(x) => {
-                self.memory[self.i as usize] = self.v[x] / 100;
-                self.memory[self.i as usize + 1] = (self.v[x] % 100) / 10;
-                self.memory[self.i as usize + 2] = self.v[x] % 10;
+                self.write_byte(self.i as usize, self.v[x] / 100)?;
+                self.write_byte(self.i as usize + 1, (self.v[x] % 100) / 10)?;
+                self.write_byte(self.i as usize + 2, self.v[x] % 10)?;
             }
             OpCode::DumpRegs(x) => {
                 for i in 0x0..=x {
-                    self.memory[self.i as usize + i] = self.v[i];
+                    self.write_byte(self.i as usize + i, self.v[i])?;
                 }
+                self.increment_i_after_load_store(x);
             }
             OpCode::LoadRegs(x) => {
                 for i in 0x0..=x {
-                    self.v[i] = self.memory[self.i as usize + i];
+                    self.v[i] = self.read_byte(self.i as usize + i)?;
+                }
+                self.increment_i_after_load_store(x);
+            }
+            OpCode::ScrollDown(n) => {
+                self.buf_scroll(0, n as isize);
+                self.draw_flag = true;
+            }
+            OpCode::ScrollRight() => {
+                self.buf_scroll(4, 0);
+                self.draw_flag = true;
+            }
+            OpCode::ScrollLeft() => {
+                self.buf_scroll(-4, 0);
+                self.draw_flag = true;
+            }
+            OpCode::Exit() => {
+                self.halted = true;
+                self.pc = self.pc.wrapping_sub(2);
+            }
+            OpCode::LowRes() => {
+                self.buf_set_hires(false);
+                self.draw_flag = true;
+            }
+            OpCode::HighRes() => {
+                self.buf_set_hires(true);
+                self.draw_flag = true;
+            }
+            OpCode::SetIToBigSprite(x) => {
+                self.i = self.big_font_location + BIG_GLYPH_LEN * (self.v[x] & 0xF) as u16;
+            }
+            OpCode::SaveFlags(x) => {
+                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+            }
+            OpCode::LoadFlags(x) => {
+                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+            }
+            OpCode::ScrollUp(n) => {
+                self.buf_scroll(0, -(n as isize));
+                self.draw_flag = true;
+            }
+            OpCode::SaveRange(x, y) => {
+                // if x > y, the registers are saved in reverse order, Vx first
+                for (k, reg) in Self::register_range(x, y).enumerate() {
+                    self.write_byte(self.i as usize + k, self.v[reg])?;
                 }
             }
+            OpCode::LoadRange(x, y) => {
+                for (k, reg) in Self::register_range(x, y).enumerate() {
+                    self.v[reg] = self.read_byte(self.i as usize + k)?;
+                }
+            }
+            OpCode::SetILong(nnnn) => {
+                self.i = nnnn;
+            }
+            OpCode::SelectPlanes(n) => {
+                self.planes = n & 0b11;
+            }
+            OpCode::LoadAudioPattern() => {
+                let mut pattern = [0; 16];
+                for (k, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_byte(self.i as usize + k)?;
+                }
+                self.audio_pattern = Some(pattern);
+            }
+            OpCode::SetPitch(x) => {
+                self.pitch = self.v[x];
+            }
+            OpCode::MachineCall(nnn) => return self.unknown_opcode(nnn),
+            OpCode::Unknown(word) => return self.unknown_opcode(word),
         }
         Ok(())
     }
@@ -351,6 +530,8 @@ impl Chip8 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{MemoryPolicy, Platform};
+    use crate::rng::FixedSequence;
 
     #[test]
     pub fn nth_nibble_test() {
@@ -383,11 +564,582 @@ mod tests {
     pub fn fetch_opcode_test() {
         let mut chip8 = Chip8::init();
 
-        // Il faudrait plus de tests de cette fonction en théorie
         chip8.memory[chip8.pc as usize] = 0x00;
         chip8.memory[(chip8.pc + 1) as usize] = 0xE0;
         let res = Chip8::fetch_opcode(&mut chip8).unwrap();
         assert_eq!(res, OpCode::ClearScreen());
     }
 
-}
\ No newline at end of file
+    #[test]
+    pub fn key_skip_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[0] = 0xA;
+
+        chip8.execute_opcode(OpCode::IsKeyPressed(0)).unwrap();
+        assert_eq!(chip8.pc, 0x200);
+
+        chip8.keypad.press(0xA);
+        chip8.execute_opcode(OpCode::IsKeyPressed(0)).unwrap();
+        assert_eq!(chip8.pc, 0x202);
+        chip8.execute_opcode(OpCode::IsKeyNPressed(0)).unwrap();
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    pub fn await_key_test() {
+        let mut chip8 = Chip8::init();
+        chip8.pc = 0x202; // as if FX0A had just been fetched
+
+        chip8.execute_opcode(OpCode::AwaitKey(3)).unwrap();
+        assert!(chip8.is_waiting_for_key());
+        assert_eq!(chip8.pc, 0x200);
+
+        // pressing is not enough, the key has to be released
+        chip8.keypad.press(0x7);
+        chip8.pc = 0x202;
+        chip8.execute_opcode(OpCode::AwaitKey(3)).unwrap();
+        assert_eq!(chip8.key_wait.unwrap().pressed, Some(0x7));
+        assert_eq!(chip8.pc, 0x200);
+
+        chip8.keypad.release(0x7);
+        chip8.pc = 0x202;
+        chip8.execute_opcode(OpCode::AwaitKey(3)).unwrap();
+        assert!(!chip8.is_waiting_for_key());
+        assert_eq!(chip8.v[3], 0x7);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    pub fn superchip_decode_test() {
+        for (platform, expected) in [(Platform::Chip8, Some(OpCode::MachineCall(0x0FF))), (Platform::SuperChip, Some(OpCode::HighRes()))] {
+            let mut chip8 = Chip8::with_platform(platform);
+            chip8.memory[0x200] = 0x00;
+            chip8.memory[0x201] = 0xFF;
+            assert_eq!(chip8.fetch_opcode().ok(), expected);
+        }
+    }
+
+    #[test]
+    pub fn superchip_draw_test() {
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip);
+        chip8.execute_opcode(OpCode::HighRes()).unwrap();
+        assert_eq!((chip8.width(), chip8.height()), (128, 64));
+
+        // 16x16 sprite, top-left pixel of each row set
+        chip8.i = 0x300;
+        for row in 0..16 {
+            chip8.memory[0x300 + 2 * row] = 0x80;
+            chip8.memory[0x300 + 2 * row + 1] = 0x01;
+        }
+        chip8.v[0] = 120;
+        chip8.v[1] = 10;
+        chip8.execute_opcode(OpCode::DrawSprite(0, 1, 0)).unwrap();
+        assert!(chip8.pixel(120, 10) && chip8.pixel(120, 25));
+        assert!(!chip8.pixel(121, 10));
+        assert!(!chip8.pixel(0, 10)); // clipped, the right column is off-screen
+
+        chip8.execute_opcode(OpCode::ScrollDown(3)).unwrap();
+        assert!(!chip8.pixel(120, 10) && chip8.pixel(120, 13));
+        chip8.execute_opcode(OpCode::ScrollLeft()).unwrap();
+        assert!(chip8.pixel(116, 13));
+        chip8.execute_opcode(OpCode::ScrollRight()).unwrap();
+        assert!(chip8.pixel(120, 13) && !chip8.pixel(116, 13));
+    }
+
+    #[test]
+    pub fn xochip_long_i_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        assert_eq!(chip8.memory.len(), 0x10000);
+        chip8.memory[0x200..0x208].copy_from_slice(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0xF0, 0x00]);
+
+        // 3000 skips the whole 4-byte F000 1234
+        let opcode = chip8.fetch_opcode().unwrap();
+        chip8.execute_opcode(opcode).unwrap();
+        assert_eq!(chip8.pc, 0x206);
+
+        chip8.pc = 0x202;
+        assert_eq!(chip8.fetch_opcode().ok(), Some(OpCode::SetILong(0x1234)));
+        assert_eq!(chip8.pc, 0x206);
+    }
+
+    #[test]
+    pub fn xochip_planes_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0x80; // plane 1 sprite
+        chip8.memory[0x301] = 0xC0; // plane 2 sprite
+
+        chip8.execute_opcode(OpCode::SelectPlanes(3)).unwrap();
+        chip8.execute_opcode(OpCode::DrawSprite(0, 0, 1)).unwrap();
+        assert_eq!(chip8.display_buf[0][0], 0b11);
+        assert_eq!(chip8.display_buf[1][0], 0b10);
+
+        // clearing plane 2 only leaves plane 1
+        chip8.execute_opcode(OpCode::SelectPlanes(2)).unwrap();
+        chip8.execute_opcode(OpCode::ClearScreen()).unwrap();
+        assert_eq!(chip8.display_buf[0][0], 0b01);
+        assert_eq!(chip8.display_buf[1][0], 0);
+
+        chip8.v[0] = 4;
+        chip8.v[1] = 8;
+        chip8.execute_opcode(OpCode::SaveRange(1, 0)).unwrap();
+        assert_eq!(&chip8.memory[0x300..0x302], &[8, 4]);
+    }
+
+    #[test]
+    pub fn quirks_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[1] = 0b0000_0011;
+        chip8.v[2] = 0b1000_0100;
+
+        chip8.quirks.shift = false;
+        chip8.execute_opcode(OpCode::StoreLSBWithShift(1, 2)).unwrap();
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0b0100_0010, 0));
+
+        chip8.quirks.shift = true;
+        chip8.execute_opcode(OpCode::StoreMSBWithShift(2, 1)).unwrap();
+        assert_eq!((chip8.v[2], chip8.v[0xF]), (0b0000_1000, 1));
+
+        chip8.v[0] = 0x10;
+        chip8.v[3] = 0x20;
+        chip8.quirks.jump = false;
+        chip8.execute_opcode(OpCode::JumpToV0Plus(0x300)).unwrap();
+        assert_eq!(chip8.pc, 0x310);
+        chip8.quirks.jump = true;
+        chip8.execute_opcode(OpCode::JumpToV0Plus(0x300)).unwrap();
+        assert_eq!(chip8.pc, 0x320);
+    }
+
+    #[test]
+    pub fn unknown_opcode_policy_test() {
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x204].copy_from_slice(&[0x01, 0x23, 0xFF, 0xFF]);
+        assert_eq!(chip8.fetch_opcode().unwrap(), OpCode::MachineCall(0x123));
+
+        chip8.unknown_opcode_policy = UnknownOpcodePolicy::Skip;
+        chip8.execute_opcode(OpCode::MachineCall(0x123)).unwrap();
+        assert_eq!(chip8.pc, 0x202);
+
+        chip8.unknown_opcode_policy = UnknownOpcodePolicy::Trap;
+        let opcode = chip8.fetch_opcode().unwrap();
+        chip8.execute_opcode(opcode).unwrap();
+        assert_eq!((chip8.pc, chip8.trapped), (0x202, Some(0x202)));
+    }
+
+    #[test]
+    pub fn errors_test() {
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0x5A, 0xB1]);
+        let opcode = chip8.fetch_opcode().unwrap();
+        assert_eq!(opcode, OpCode::Unknown(0x5AB1));
+        assert!(matches!(chip8.execute_opcode(opcode), Err(Chip8Error::UnknownOpcode { addr: 0x200, word: 0x5AB1 })));
+        assert_eq!(chip8.pc, 0x200);
+
+        chip8.pc = 0x202;
+        assert!(matches!(chip8.execute_opcode(OpCode::Return()), Err(Chip8Error::StackUnderflow { addr: 0x200 })));
+
+        assert_eq!(chip8.stack.depth(), 12);
+        for _ in 0..12 {
+            chip8.execute_opcode(OpCode::CallSubroutine(0x300)).unwrap();
+        }
+        assert!(matches!(chip8.execute_opcode(OpCode::CallSubroutine(0x300)), Err(Chip8Error::StackOverflow { .. })));
+
+        chip8.i = 0xFFE;
+        chip8.v[0] = 0xFF;
+        chip8.execute_opcode(OpCode::ToDecimal(0)).unwrap(); // wraps around by default
+        assert_eq!((chip8.memory[0xFFE], chip8.memory[0xFFF], chip8.memory[0x000]), (2, 5, 5));
+        chip8.memory_policy = MemoryPolicy::Error;
+        assert!(matches!(chip8.execute_opcode(OpCode::ToDecimal(0)), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, size: 0x1000 })));
+    }
+
+    /// Fetches and executes `word` at PC, like `step` does.
+    fn exec(chip8: &mut Chip8, word: u16) {
+        let pc = chip8.pc as usize;
+        chip8.memory[pc..pc + 2].copy_from_slice(&word.to_be_bytes());
+        let opcode = chip8.fetch_opcode().unwrap();
+        chip8.execute_opcode(opcode).unwrap();
+    }
+
+    #[test]
+    pub fn decode_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        let words = [
+            (0x00E0, OpCode::ClearScreen()), (0x00EE, OpCode::Return()), (0x1ABC, OpCode::Jump(0xABC)),
+            (0x2ABC, OpCode::CallSubroutine(0xABC)), (0x3A12, OpCode::CondEq(0xA, 0x12)), (0x4A12, OpCode::CondNEq(0xA, 0x12)),
+            (0x5AB0, OpCode::CondEqReg(0xA, 0xB)), (0x6A12, OpCode::SetReg(0xA, 0x12)), (0x7A12, OpCode::AddToReg(0xA, 0x12)),
+            (0x8AB0, OpCode::AssignRegToReg(0xA, 0xB)), (0x8AB1, OpCode::BitwiseOr(0xA, 0xB)), (0x8AB2, OpCode::BitwiseAnd(0xA, 0xB)),
+            (0x8AB3, OpCode::BitwiseXor(0xA, 0xB)), (0x8AB4, OpCode::AddRegToReg(0xA, 0xB)), (0x8AB5, OpCode::SubRegToReg(0xA, 0xB)),
+            (0x8AB6, OpCode::StoreLSBWithShift(0xA, 0xB)), (0x8AB7, OpCode::SubRegFromReg(0xA, 0xB)),
+            (0x8ABE, OpCode::StoreMSBWithShift(0xA, 0xB)), (0x9AB0, OpCode::CondNEqReg(0xA, 0xB)), (0xAABC, OpCode::SetI(0xABC)),
+            (0xBABC, OpCode::JumpToV0Plus(0xABC)), (0xCA12, OpCode::RegRandBitwiseAnd(0xA, 0x12)),
+            (0xDAB5, OpCode::DrawSprite(0xA, 0xB, 5)), (0xEA9E, OpCode::IsKeyPressed(0xA)), (0xEAA1, OpCode::IsKeyNPressed(0xA)),
+            (0xFA07, OpCode::SetRegToTimer(0xA)), (0xFA0A, OpCode::AwaitKey(0xA)), (0xFA15, OpCode::SetDelayTimer(0xA)),
+            (0xFA18, OpCode::SetSoundTimer(0xA)), (0xFA1E, OpCode::AddRegToI(0xA)), (0xFA29, OpCode::SetIToSprite(0xA)),
+            (0xFA33, OpCode::ToDecimal(0xA)), (0xFA55, OpCode::DumpRegs(0xA)), (0xFA65, OpCode::LoadRegs(0xA)),
+            (0x00C5, OpCode::ScrollDown(5)), (0x00FB, OpCode::ScrollRight()), (0x00FC, OpCode::ScrollLeft()),
+            (0x00FD, OpCode::Exit()), (0x00FE, OpCode::LowRes()), (0x00FF, OpCode::HighRes()),
+            (0xFA30, OpCode::SetIToBigSprite(0xA)), (0xFA75, OpCode::SaveFlags(0xA)), (0xFA85, OpCode::LoadFlags(0xA)),
+            (0x00D5, OpCode::ScrollUp(5)), (0x5AB2, OpCode::SaveRange(0xA, 0xB)), (0x5AB3, OpCode::LoadRange(0xA, 0xB)),
+            (0xF201, OpCode::SelectPlanes(2)), (0xF002, OpCode::LoadAudioPattern()), (0xFA3A, OpCode::SetPitch(0xA)),
+            (0x0123, OpCode::MachineCall(0x123)), (0x8AB8, OpCode::Unknown(0x8AB8)), (0x9AB1, OpCode::Unknown(0x9AB1)),
+            (0xEA00, OpCode::Unknown(0xEA00)), (0xFAFF, OpCode::Unknown(0xFAFF)),
+        ];
+        for (word, expected) in words {
+            chip8.pc = 0x200;
+            chip8.memory[0x200..0x202].copy_from_slice(&u16::to_be_bytes(word));
+            assert_eq!(chip8.fetch_opcode().unwrap(), expected, "{:04X}", word);
+            assert_eq!(chip8.pc, 0x202);
+        }
+
+        // the SUPER-CHIP and XO-CHIP instructions are unknown on older platforms
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip);
+        for (word, expected) in [(0x00D5, OpCode::MachineCall(0x0D5)), (0x5AB2, OpCode::Unknown(0x5AB2)), (0xF201, OpCode::Unknown(0xF201))] {
+            chip8.pc = 0x200;
+            chip8.memory[0x200..0x202].copy_from_slice(&u16::to_be_bytes(word));
+            assert_eq!(chip8.fetch_opcode().unwrap(), expected);
+        }
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0xFA, 0x30]);
+        assert_eq!(chip8.fetch_opcode().unwrap(), OpCode::Unknown(0xFA30));
+    }
+
+    #[test]
+    pub fn flow_test() {
+        let mut chip8 = Chip8::init();
+        exec(&mut chip8, 0x1300);
+        assert_eq!(chip8.pc, 0x300);
+
+        exec(&mut chip8, 0x2400);
+        assert_eq!(chip8.pc, 0x400);
+        assert_eq!(chip8.stack.entries(), &[0x302]);
+        exec(&mut chip8, 0x2500);
+        assert_eq!(chip8.stack.entries(), &[0x302, 0x402]);
+        exec(&mut chip8, 0x00EE);
+        assert_eq!(chip8.pc, 0x402);
+        exec(&mut chip8, 0x00EE);
+        assert_eq!(chip8.pc, 0x302);
+        assert!(chip8.stack.is_empty());
+
+        chip8.v[0] = 0xFF;
+        exec(&mut chip8, 0xBF01);
+        assert_eq!(chip8.pc, 0x1000); // BNNN can jump past 0xFFF, memory accesses wrap
+
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip);
+        exec(&mut chip8, 0x00FD);
+        assert!(chip8.halted);
+        assert_eq!(chip8.pc, 0x200);
+    }
+
+    #[test]
+    pub fn skip_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[1] = 0x42;
+        chip8.v[2] = 0x42;
+        chip8.v[3] = 0x43;
+        for (word, skips) in [
+            (0x3142, true), (0x3143, false), (0x4142, false), (0x4143, true),
+            (0x5120, true), (0x5130, false), (0x9120, false), (0x9130, true),
+        ] {
+            chip8.pc = 0x200;
+            exec(&mut chip8, word);
+            assert_eq!(chip8.pc, if skips { 0x204 } else { 0x202 }, "{:04X}", word);
+        }
+
+        // skipping F000 NNNN skips 4 bytes on XO-CHIP only
+        for (platform, pc) in [(Platform::Chip8, 0x204), (Platform::XoChip, 0x206)] {
+            let mut chip8 = Chip8::with_platform(platform);
+            chip8.memory[0x202..0x204].copy_from_slice(&[0xF0, 0x00]);
+            exec(&mut chip8, 0x3000);
+            assert_eq!(chip8.pc, pc);
+        }
+    }
+
+    #[test]
+    pub fn register_ops_test() {
+        let mut chip8 = Chip8::init();
+        exec(&mut chip8, 0x6AFE);
+        assert_eq!(chip8.v[0xA], 0xFE);
+        chip8.v[0xF] = 7;
+        exec(&mut chip8, 0x7A03);
+        assert_eq!((chip8.v[0xA], chip8.v[0xF]), (0x01, 7)); // 7XNN wraps and leaves VF alone
+
+        chip8.v[1] = 0b1100;
+        chip8.v[2] = 0b1010;
+        for (word, expected) in [(0x8120, 0b1010), (0x8121, 0b1110), (0x8122, 0b1000), (0x8123, 0b0110)] {
+            chip8.v[1] = 0b1100;
+            exec(&mut chip8, word);
+            assert_eq!(chip8.v[1], expected, "{:04X}", word);
+        }
+    }
+
+    #[test]
+    pub fn arithmetic_flags_test() {
+        let mut chip8 = Chip8::init();
+        // Vx, Vy, instruction, Vx after, VF after
+        for (vx, vy, word, res, flag) in [
+            (0xF0, 0x0F, 0x8124, 0xFF, 0), (0xF0, 0x20, 0x8124, 0x10, 1),
+            (0x30, 0x10, 0x8125, 0x20, 1), (0x10, 0x10, 0x8125, 0x00, 1), (0x10, 0x30, 0x8125, 0xE0, 0),
+            (0x10, 0x30, 0x8127, 0x20, 1), (0x10, 0x10, 0x8127, 0x00, 1), (0x30, 0x10, 0x8127, 0xE0, 0),
+        ] {
+            chip8.v[1] = vx;
+            chip8.v[2] = vy;
+            chip8.v[0xF] = 0x55;
+            exec(&mut chip8, word);
+            assert_eq!((chip8.v[1], chip8.v[0xF]), (res, flag), "{:04X} with {:02X}, {:02X}", word, vx, vy);
+        }
+    }
+
+    #[test]
+    pub fn vf_operand_test() {
+        // the flag is written last, so when VF is the destination it ends up holding the flag
+        let mut chip8 = Chip8::init();
+        for (vf, vy, word, flag) in [
+            (0xFF, 0x01, 0x8F14, 1), (0x01, 0x01, 0x8F14, 0),
+            (0x01, 0x02, 0x8F15, 0), (0x02, 0x01, 0x8F15, 1),
+            (0x02, 0x01, 0x8F17, 0), (0x01, 0x02, 0x8F17, 1),
+            (0x01, 0x01, 0x8F16, 1), (0x80, 0x80, 0x8F1E, 1),
+        ] {
+            chip8.v[0xF] = vf;
+            chip8.v[1] = vy;
+            exec(&mut chip8, word);
+            assert_eq!(chip8.v[0xF], flag, "{:04X}", word);
+        }
+
+        // VF as the source is read before the flag overwrites it
+        chip8.v[1] = 0x10;
+        chip8.v[0xF] = 0xF0;
+        exec(&mut chip8, 0x81F4);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0x00, 1));
+        chip8.v[1] = 0x10;
+        chip8.v[0xF] = 0x01;
+        exec(&mut chip8, 0x81F5);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0x0F, 1));
+        chip8.v[0xF] = 0x03;
+        exec(&mut chip8, 0x81F6);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0x01, 1));
+    }
+
+    #[test]
+    pub fn vf_reset_test() {
+        for vf_reset in [true, false] {
+            let mut chip8 = Chip8::init();
+            chip8.quirks.vf_reset = vf_reset;
+            for word in [0x8121, 0x8122, 0x8123] {
+                chip8.v[0xF] = 0x55;
+                exec(&mut chip8, word);
+                assert_eq!(chip8.v[0xF], if vf_reset { 0 } else { 0x55 }, "{:04X}", word);
+            }
+        }
+    }
+
+    #[test]
+    pub fn shift_test() {
+        let mut chip8 = Chip8::init();
+        chip8.quirks.shift = false;
+        chip8.v[1] = 0xFF;
+        chip8.v[2] = 0b1000_0001;
+        exec(&mut chip8, 0x8126);
+        assert_eq!((chip8.v[1], chip8.v[2], chip8.v[0xF]), (0b0100_0000, 0b1000_0001, 1));
+        exec(&mut chip8, 0x812E);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0b0000_0010, 1));
+
+        chip8.quirks.shift = true;
+        chip8.v[1] = 0b0100_0010;
+        exec(&mut chip8, 0x8126);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0b0010_0001, 0));
+        exec(&mut chip8, 0x812E);
+        assert_eq!((chip8.v[1], chip8.v[0xF]), (0b0100_0010, 0));
+    }
+
+    #[test]
+    pub fn random_test() {
+        let mut chip8 = Chip8::init();
+        chip8.seed_rng(1);
+        let mut seen = [false; 256];
+        for _ in 0..4096 {
+            chip8.pc = 0x200;
+            exec(&mut chip8, 0xC1FF);
+            seen[chip8.v[1] as usize] = true;
+            exec(&mut chip8, 0xC20F);
+            assert_eq!(chip8.v[2] & 0xF0, 0);
+        }
+        assert!(seen.iter().all(|&s| s), "every byte, 255 included, is produced");
+
+        // the same seed gives the same numbers
+        let mut other = Chip8::init();
+        other.seed_rng(1);
+        exec(&mut other, 0xC1FF);
+        chip8.seed_rng(1);
+        chip8.pc = 0x200;
+        exec(&mut chip8, 0xC1FF);
+        assert_eq!(chip8.v[1], other.v[1]);
+
+        chip8.rng = Box::new(FixedSequence::new(&[0xFF, 0x5A]));
+        chip8.pc = 0x200;
+        exec(&mut chip8, 0xC10F);
+        exec(&mut chip8, 0xC2F0);
+        assert_eq!((chip8.v[1], chip8.v[2]), (0x0F, 0x50));
+    }
+
+    #[test]
+    pub fn timers_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[1] = 30;
+        exec(&mut chip8, 0xF115);
+        exec(&mut chip8, 0xF118);
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (30, 30));
+        chip8.tick_timers();
+        exec(&mut chip8, 0xF207);
+        assert_eq!(chip8.v[2], 29);
+    }
+
+    #[test]
+    pub fn index_test() {
+        let mut chip8 = Chip8::init();
+        exec(&mut chip8, 0xAFFE);
+        assert_eq!(chip8.i, 0xFFE);
+        chip8.v[1] = 0x03;
+        chip8.v[0xF] = 0x55;
+        exec(&mut chip8, 0xF11E);
+        assert_eq!((chip8.i, chip8.v[0xF]), (0x1001, 0x55)); // no overflow flag
+
+        chip8.v[1] = 0x1A; // only the low nibble selects the glyph
+        exec(&mut chip8, 0xF129);
+        assert_eq!(chip8.i, chip8.font_location + 5 * 0xA);
+
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip);
+        chip8.v[1] = 0x3;
+        exec(&mut chip8, 0xF130);
+        assert_eq!(chip8.i, chip8.big_font_location + 10 * 3);
+
+        let mut chip8 = Chip8::init();
+        chip8.v[1] = 254;
+        chip8.i = 0x300;
+        exec(&mut chip8, 0xF133);
+        assert_eq!(&chip8.memory[0x300..0x303], &[2, 5, 4]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    pub fn load_store_test() {
+        for (increment, i) in [(MemoryIncrement::XPlusOne, 0x303), (MemoryIncrement::X, 0x302), (MemoryIncrement::None, 0x300)] {
+            let mut chip8 = Chip8::init();
+            chip8.quirks.memory_increment = increment;
+            chip8.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+            chip8.i = 0x300;
+            exec(&mut chip8, 0xF255);
+            assert_eq!(&chip8.memory[0x300..0x304], &[1, 2, 3, 0]);
+            assert_eq!(chip8.i, i);
+
+            chip8.v = [0; 16];
+            chip8.i = 0x300;
+            exec(&mut chip8, 0xF265);
+            assert_eq!(&chip8.v[..4], &[1, 2, 3, 0]);
+            assert_eq!(chip8.i, i);
+        }
+    }
+
+    #[test]
+    pub fn draw_test() {
+        let mut chip8 = Chip8::init();
+        chip8.quirks.display_wait = false;
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0b1100_0000;
+        chip8.v[1] = 62;
+        chip8.v[2] = 31;
+
+        exec(&mut chip8, 0xD121);
+        assert_eq!(chip8.v[0xF], 0);
+        assert!(chip8.draw_flag);
+        assert!(chip8.pixel(62, 31) && chip8.pixel(63, 31));
+        exec(&mut chip8, 0xD121);
+        assert_eq!(chip8.v[0xF], 1); // collision, the pixels are erased
+        assert!(!chip8.pixel(62, 31));
+
+        // coordinates wrap, the sprite itself is clipped or wraps with the clip quirk
+        chip8.v[1] = 64 + 63;
+        chip8.quirks.clip = true;
+        exec(&mut chip8, 0xD121);
+        assert!(chip8.pixel(63, 31) && !chip8.pixel(0, 31));
+        exec(&mut chip8, 0x00E0);
+        chip8.quirks.clip = false;
+        exec(&mut chip8, 0xD121);
+        assert!(chip8.pixel(63, 31) && chip8.pixel(0, 31));
+
+        exec(&mut chip8, 0x00E0);
+        assert!(!chip8.pixel(63, 31) && !chip8.pixel(0, 31));
+    }
+
+    #[test]
+    pub fn display_wait_test() {
+        let mut chip8 = Chip8::init();
+        chip8.quirks.display_wait = true;
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0x80;
+        exec(&mut chip8, 0xD011);
+        assert_eq!(chip8.pc, 0x202);
+        assert!(chip8.pixel(0, 0));
+
+        // the second sprite of the frame waits for the next one
+        exec(&mut chip8, 0xD011);
+        assert_eq!(chip8.pc, 0x202);
+        assert!(chip8.pixel(0, 0));
+        chip8.tick_timers();
+        exec(&mut chip8, 0xD011);
+        assert_eq!(chip8.pc, 0x204);
+        assert!(!chip8.pixel(0, 0));
+    }
+
+    #[test]
+    pub fn waits_at_end_of_memory_test() {
+        // the instructions that wait move PC back over the end of memory
+        for word in [0xD011, 0xF00A, 0x00FD] {
+            let mut chip8 = Chip8::with_platform(Platform::XoChip);
+            chip8.quirks.display_wait = true;
+            chip8.vblank = false;
+            chip8.pc = 0xFFFE;
+            exec(&mut chip8, word);
+            assert_eq!(chip8.pc, 0xFFFE);
+        }
+    }
+
+    #[test]
+    pub fn superchip_ops_test() {
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip);
+        exec(&mut chip8, 0x00FF);
+        assert!(chip8.hires);
+        exec(&mut chip8, 0x00FE);
+        assert!(!chip8.hires);
+
+        chip8.v[..3].copy_from_slice(&[7, 8, 9]);
+        exec(&mut chip8, 0xF275);
+        chip8.v = [0; 16];
+        exec(&mut chip8, 0xF185);
+        assert_eq!(&chip8.v[..3], &[7, 8, 0]);
+    }
+
+    #[test]
+    pub fn xochip_ops_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.display_buf[0][5] = 1;
+        exec(&mut chip8, 0x00D3);
+        assert!(chip8.pixel(0, 2) && !chip8.pixel(0, 5));
+
+        chip8.v[2..5].copy_from_slice(&[1, 2, 3]);
+        chip8.i = 0x300;
+        exec(&mut chip8, 0x5242);
+        assert_eq!(&chip8.memory[0x300..0x303], &[1, 2, 3]);
+        assert_eq!(chip8.i, 0x300);
+        exec(&mut chip8, 0x5423);
+        assert_eq!(&chip8.v[2..5], &[3, 2, 1]);
+
+        for k in 0..16 {
+            chip8.memory[0x300 + k] = k as u8;
+        }
+        exec(&mut chip8, 0xF002);
+        assert_eq!(chip8.audio_pattern.unwrap()[15], 15);
+        chip8.v[1] = 112;
+        exec(&mut chip8, 0xF13A);
+        assert_eq!(chip8.pitch, 112);
+    }
+}
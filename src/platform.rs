@@ -0,0 +1,65 @@
+use std::fmt;
+
+
+/// CHIP-8 variant a ROM was written for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    #[default]
+    Chip8,     // original COSMAC VIP interpreter
+    SuperChip, // SUPER-CHIP 1.1 (HP48): 128x64 hi-res, scrolling, big font, RPL flags
+    XoChip,    // Octo's XO-CHIP: SUPER-CHIP plus 64KB memory, two bitplanes and audio patterns
+}
+
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name.to_lowercase().as_str() {
+            "chip8" | "chip-8" | "vip" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" | "octo" => Some(Platform::XoChip),
+            _ => None
+        }
+    }
+
+    /// true if the SUPER-CHIP instructions are decoded
+    pub fn has_superchip(&self) -> bool {
+        *self != Platform::Chip8
+    }
+
+    /// true if the XO-CHIP instructions are decoded
+    pub fn has_xochip(&self) -> bool {
+        *self == Platform::XoChip
+    }
+
+    /// Size of the addressable memory, in bytes.
+    pub fn memory_size(&self) -> usize {
+        if self.has_xochip() { 0x10000 } else { 0x1000 }
+    }
+
+    /// Nesting level of subroutine calls the interpreter supports.
+    pub fn stack_depth(&self) -> usize {
+        if self.has_superchip() { 16 } else { 12 }
+    }
+
+    /// Largest screen resolution of the platform, in pixels.
+    pub fn max_resolution(&self) -> (usize, usize) {
+        if self.has_superchip() { (128, 64) } else { (64, 32) }
+    }
+}
+
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
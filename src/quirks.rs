@@ -0,0 +1,182 @@
+use crate::Platform;
+
+
+/// How FX55/FX65 leave the index register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    XPlusOne, // I += X + 1 (COSMAC VIP, XO-CHIP)
+    X,        // I += X (CHIP-48)
+    None,     // I unchanged (SUPER-CHIP 1.1)
+}
+
+
+/// Behaviour of the instructions that differ between CHIP-8 interpreters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift: bool,                       // 8XY6/8XYE shift Vx in place, otherwise Vx = Vy shifted
+    pub memory_increment: MemoryIncrement, // FX55/FX65 effect on I
+    pub jump: bool,                        // BXNN jumps to XNN + Vx, otherwise BNNN jumps to NNN + V0
+    pub vf_reset: bool,                    // 8XY1/8XY2/8XY3 reset VF to 0
+    pub clip: bool,                        // sprites are clipped at the screen edges, otherwise they wrap around
+    pub display_wait: bool,                // DXYN waits for the next frame (vertical blank), one sprite per frame
+}
+
+
+impl Quirks {
+    pub const PRESETS: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+    /// Original COSMAC VIP interpreter.
+    pub fn vip() -> Quirks {
+        Quirks {
+            shift: false,
+            memory_increment: MemoryIncrement::XPlusOne,
+            jump: false,
+            vf_reset: true,
+            clip: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 on the HP48.
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift: true,
+            memory_increment: MemoryIncrement::X,
+            jump: true,
+            vf_reset: false,
+            clip: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Quirks {
+        Quirks {
+            memory_increment: MemoryIncrement::None,
+            ..Quirks::chip48()
+        }
+    }
+
+    /// Octo's XO-CHIP.
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift: false,
+            memory_increment: MemoryIncrement::XPlusOne,
+            jump: false,
+            vf_reset: false,
+            clip: false,
+            display_wait: false,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name.to_lowercase().as_str() {
+            "vip" | "chip8" | "chip-8" => Some(Quirks::vip()),
+            "chip48" | "chip-48" => Some(Quirks::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Quirks::schip()),
+            "xochip" | "xo-chip" | "octo" => Some(Quirks::xochip()),
+            _ => None
+        }
+    }
+
+    /// Quirks of the reference interpreter of `platform`.
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::Chip8 => Quirks::vip(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+
+    /// Applies a single `<quirk>=<value>` setting, such as `shift=on` or `memory=x`.
+    pub fn apply(&mut self, setting: &str) -> Result<(), String> {
+        let (name, value) = setting.split_once('=')
+            .ok_or_else(|| format!("expected `<quirk>=<value>`, got `{}`", setting))?;
+        let (name, value) = (name.trim(), value.trim().to_lowercase());
+
+        if name == "memory" {
+            self.memory_increment = match value.as_str() {
+                "x+1" => MemoryIncrement::XPlusOne,
+                "x" => MemoryIncrement::X,
+                "none" => MemoryIncrement::None,
+                _ => return Err(format!("memory quirk expects x+1, x or none, got `{}`", value)),
+            };
+            return Ok(());
+        }
+
+        let enabled = match value.as_str() {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("{} quirk expects on or off, got `{}`", name, value)),
+        };
+        match name {
+            "shift" => self.shift = enabled,
+            "jump" => self.jump = enabled,
+            "vf-reset" => self.vf_reset = enabled,
+            "clip" => self.clip = enabled,
+            "display-wait" => self.display_wait = enabled,
+            _ => return Err(format!("unknown quirk `{}`", name)),
+        }
+        Ok(())
+    }
+
+    /// Every quirk as an `apply` setting.
+    pub fn settings(&self) -> Vec<String> {
+        let on_off = |enabled| if enabled { "on" } else { "off" };
+        let memory = match self.memory_increment {
+            MemoryIncrement::XPlusOne => "x+1",
+            MemoryIncrement::X => "x",
+            MemoryIncrement::None => "none",
+        };
+        vec![
+            format!("shift={}", on_off(self.shift)),
+            format!("jump={}", on_off(self.jump)),
+            format!("vf-reset={}", on_off(self.vf_reset)),
+            format!("clip={}", on_off(self.clip)),
+            format!("display-wait={}", on_off(self.display_wait)),
+            format!("memory={}", memory),
+        ]
+    }
+}
+
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::vip()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn presets_test() {
+        for name in Quirks::PRESETS {
+            assert!(Quirks::preset(name).is_some(), "{}", name);
+        }
+        for platform in Platform::ALL {
+            assert_eq!(Quirks::preset(platform.name()), Some(Quirks::for_platform(platform)));
+        }
+    }
+
+    #[test]
+    pub fn apply_test() {
+        let mut quirks = Quirks::vip();
+        quirks.apply("shift=on").unwrap();
+        quirks.apply("memory=none").unwrap();
+        quirks.apply("clip = off").unwrap();
+        assert_eq!(quirks, Quirks { shift: true, memory_increment: MemoryIncrement::None, clip: false, ..Quirks::vip() });
+
+        assert!(quirks.apply("shift=maybe").is_err());
+        assert!(quirks.apply("warp=on").is_err());
+        assert!(quirks.apply("shift").is_err());
+
+        let mut other = Quirks::xochip();
+        for setting in quirks.settings() {
+            other.apply(&setting).unwrap();
+        }
+        assert_eq!(other, quirks);
+    }
+}
@@ -0,0 +1,194 @@
+//! History of the last frames, to run a game backwards.
+
+use std::collections::VecDeque;
+use crate::Chip8;
+
+
+/// Ring buffer of per-frame snapshots.
+///
+/// Only the newest frame is kept whole. Each older frame is stored as its XOR with the
+/// frame after it, with the runs of zeroes left out, which is a few bytes when little
+/// changed.
+pub struct Rewind {
+    newest: Option<Vec<u8>>,        // snapshot of the last recorded frame
+    deltas: VecDeque<(usize, Vec<u8>)>, // older frames, oldest first: snapshot length and encoded XOR with the next one
+    capacity: usize,                // frames kept, the newest included
+}
+
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind { newest: None, deltas: VecDeque::new(), capacity }
+    }
+
+    /// Frames that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    /// Records the state of `chip8` as the newest frame, forgetting the oldest one when full.
+    pub fn record(&mut self, chip8: &Chip8) {
+        if self.capacity == 0 {
+            return;
+        }
+        let snapshot = chip8.snapshot();
+        if let Some(previous) = self.newest.replace(snapshot) {
+            let delta = xor(&previous, self.newest.as_ref().unwrap());
+            self.deltas.push_back((previous.len(), encode_delta(&delta)));
+        }
+        while self.deltas.len() >= self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Restores the frame before the newest one, which then becomes the newest. false if
+    /// there is no older frame.
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> bool {
+        let (Some(newest), Some((len, delta))) = (&self.newest, self.deltas.pop_back()) else {
+            return false;
+        };
+        let Some(delta) = decode_delta(&delta, len) else {
+            self.clear();
+            return false;
+        };
+        let previous = xor(&delta, newest);
+        if chip8.load_state(&previous).is_err() {
+            self.clear();
+            return false;
+        }
+        chip8.draw_flag = true;
+        self.newest = Some(previous);
+        true
+    }
+}
+
+
+/// `a` XOR `b`, `b` being cut or padded with zeroes to the length of `a`.
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().enumerate().map(|(k, byte)| byte ^ b.get(k).unwrap_or(&0)).collect()
+}
+
+
+/// Sparse encoding of an XOR: `<zeroes> <length> <bytes>` groups, counts as LEB128.
+fn encode_delta(delta: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < delta.len() {
+        let zeroes = delta[pos..].iter().take_while(|&&b| b == 0).count();
+        pos += zeroes;
+        // literals end at the first run of 4 zeroes, cheaper to skip than to store
+        let start = pos;
+        while pos < delta.len() && delta[pos..].iter().take(4).any(|&b| b != 0) {
+            pos += 1;
+        }
+        write_count(&mut out, zeroes);
+        write_count(&mut out, pos - start);
+        out.extend_from_slice(&delta[start..pos]);
+    }
+    out
+}
+
+
+fn decode_delta(data: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut bytes = data.iter().copied();
+    while let Some(zeroes) = read_count(&mut bytes) {
+        out.resize(out.len() + zeroes, 0);
+        for _ in 0..read_count(&mut bytes)? {
+            out.push(bytes.next()?);
+        }
+        if out.len() > len {
+            return None;
+        }
+    }
+    out.resize(len, 0);
+    Some(out)
+}
+
+
+fn write_count(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+
+fn read_count(bytes: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut n = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = bytes.next()?;
+        n |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Platform;
+
+    #[test]
+    pub fn rewind_test() {
+        let mut chip8 = Chip8::init();
+        // 7001 (V0 += 1), 2206 (call), 1200 (loop), 00EE (return)
+        chip8.memory[0x200..0x208].copy_from_slice(&[0x70, 0x01, 0x22, 0x06, 0x12, 0x00, 0x00, 0xEE]);
+        let mut rewind = Rewind::new(4);
+        rewind.record(&chip8);
+        for _ in 0..6 {
+            chip8.run_frame(2).unwrap();
+            rewind.record(&chip8);
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!((chip8.v[0], chip8.stack.len()), (3, 0));
+
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!((chip8.v[0], chip8.stack.len()), (3, 1));
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!((chip8.v[0], chip8.pc), (2, 0x200));
+        assert!(rewind.step_back(&mut chip8));
+        assert!(!rewind.step_back(&mut chip8));
+        assert_eq!(chip8.v[0], 2);
+
+        // resuming records from the restored frame on
+        chip8.run_frame(2).unwrap();
+        rewind.record(&chip8);
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!(chip8.v[0], 2);
+    }
+
+    #[test]
+    pub fn delta_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        let mut rewind = Rewind::new(100);
+        rewind.record(&chip8);
+        chip8.v[3] = 7;
+        chip8.memory[0x8000] = 1;
+        rewind.record(&chip8);
+        // 74KB snapshots, two bytes apart
+        assert!(rewind.deltas[0].1.len() < 16, "{} bytes", rewind.deltas[0].1.len());
+
+        for delta in [vec![], vec![0; 300], vec![1, 0, 0, 2, 0, 0, 0, 0, 0, 3], (0..=255).collect()] {
+            assert_eq!(decode_delta(&encode_delta(&delta), delta.len()), Some(delta));
+        }
+        assert_eq!(decode_delta(&[0, 3, 1], 4), None);
+
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!((chip8.v[3], chip8.memory[0x8000]), (0, 0));
+        assert!(Rewind::new(0).is_empty());
+    }
+}
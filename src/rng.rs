@@ -0,0 +1,136 @@
+//! Random number sources for CXNN.
+
+
+/// Where CXNN gets its random bytes from.
+///
+/// `state` returns a seed that continues the sequence from the current position, so that
+/// save states restore it exactly.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+    fn seed(&mut self, seed: u64);
+    fn state(&self) -> u64;
+    fn box_clone(&self) -> Box<dyn RandomSource>;
+}
+
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Box<dyn RandomSource> {
+        self.box_clone()
+    }
+}
+
+
+/// SplitMix64: fast, and its whole state is the 64-bit seed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    /// Seeded from the OS.
+    pub fn from_entropy() -> SplitMix64 {
+        SplitMix64::new(rand::random())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+
+impl RandomSource for SplitMix64 {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(*self)
+    }
+}
+
+
+/// Stub for tests: repeats `bytes` over and over. The seed is the position in `bytes`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedSequence {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+
+impl FixedSequence {
+    /// `bytes` must not be empty.
+    pub fn new(bytes: &[u8]) -> FixedSequence {
+        assert!(!bytes.is_empty(), "a fixed sequence needs at least one byte");
+        FixedSequence { bytes: bytes.to_vec(), pos: 0 }
+    }
+}
+
+
+impl RandomSource for FixedSequence {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.pos];
+        self.pos = (self.pos + 1) % self.bytes.len();
+        byte
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.pos = (seed % self.bytes.len() as u64) as usize;
+    }
+
+    fn state(&self) -> u64 {
+        self.pos as u64
+    }
+
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn split_mix_test() {
+        // reference values of SplitMix64 seeded with 0
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+
+        // the state continues the sequence
+        let mut other = SplitMix64::new(rng.state());
+        assert_eq!(other.next_byte(), rng.next_byte());
+    }
+
+    #[test]
+    pub fn fixed_sequence_test() {
+        let mut rng = FixedSequence::new(&[1, 2, 3]);
+        let bytes: Vec<u8> = (0..5).map(|_| rng.next_byte()).collect();
+        assert_eq!(bytes, [1, 2, 3, 1, 2]);
+        assert_eq!(rng.state(), 2);
+        rng.seed(4);
+        assert_eq!(rng.next_byte(), 2);
+
+        let mut boxed: Box<dyn RandomSource> = Box::new(rng);
+        let mut cloned = boxed.clone();
+        assert_eq!(boxed.next_byte(), cloned.next_byte());
+    }
+}
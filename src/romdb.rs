@@ -0,0 +1,262 @@
+//! ROM database in the format of the community chip-8-database
+//! (https://github.com/chip-8/chip-8-database): a `programs.json` array of programs, each
+//! with the ROMs it was released as keyed by the SHA-1 of their bytes.
+
+use crate::json::{self, Value};
+use crate::quirks::MemoryIncrement;
+use crate::{Platform, Quirks};
+
+
+/// Entries for the ROMs in `games/`.
+const BUNDLED: &str = include_str!("../data/programs.json");
+
+
+/// What the database knows about a ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub tickrate: Option<u32>,     // instructions per frame
+    pub keys: Vec<(String, u8)>,   // hex key of each role (`up`, `left`, `a`, ...)
+    pub palette: Option<[u32; 4]>, // 0xRRGGBB colours of the pixel values
+}
+
+
+pub struct RomDatabase {
+    roms: Vec<([u8; 20], RomInfo)>,
+}
+
+
+impl RomDatabase {
+    pub fn empty() -> RomDatabase {
+        RomDatabase { roms: Vec::new() }
+    }
+
+    /// Database of the ROMs shipped in `games/`.
+    pub fn bundled() -> RomDatabase {
+        RomDatabase::parse(BUNDLED).expect("bundled ROM database is valid")
+    }
+
+    /// Reads a `programs.json` file. ROMs that only run on unsupported platforms are left out.
+    pub fn parse(text: &str) -> Result<RomDatabase, String> {
+        let programs = json::parse(text)?;
+        let programs = programs.as_array().ok_or("expected an array of programs")?;
+
+        let mut roms = Vec::new();
+        for program in programs {
+            let title = program.get("title").and_then(Value::as_str).unwrap_or_default();
+            let Some(entries) = program.get("roms").and_then(Value::as_object) else {
+                continue;
+            };
+            for (hash, rom) in entries {
+                let hash = parse_hash(hash).ok_or_else(|| format!("{}: invalid SHA-1 `{}`", title, hash))?;
+                if let Some(info) = rom_info(title, rom) {
+                    roms.push((hash, info));
+                }
+            }
+        }
+        Ok(RomDatabase { roms })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        let hash = sha1(rom);
+        self.roms.iter().find(|(h, _)| *h == hash).map(|(_, info)| info)
+    }
+}
+
+
+/// Platform and quirks of a database platform id.
+fn platform_from_id(id: &str) -> Option<(Platform, Quirks)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some((Platform::Chip8, Quirks::vip())),
+        "modernChip8" => Some((Platform::Chip8, Quirks { vf_reset: false, display_wait: false, ..Quirks::vip() })),
+        "chip48" => Some((Platform::Chip8, Quirks::chip48())),
+        "superchip1" | "superchip" => Some((Platform::SuperChip, Quirks::schip())),
+        "xochip" => Some((Platform::XoChip, Quirks::xochip())),
+        _ => None
+    }
+}
+
+
+/// Applies a database quirk (`quirkyPlatforms` entry) on top of `quirks`.
+fn apply_quirk(quirks: &mut Quirks, name: &str, enabled: bool) {
+    match name {
+        "shift" => quirks.shift = enabled,
+        "memoryIncrementByX" if enabled => quirks.memory_increment = MemoryIncrement::X,
+        "memoryLeaveIUnchanged" if enabled => quirks.memory_increment = MemoryIncrement::None,
+        "wrap" => quirks.clip = !enabled,
+        "jump" => quirks.jump = enabled,
+        "vblank" => quirks.display_wait = enabled,
+        "logic" => quirks.vf_reset = enabled,
+        _ => {}
+    }
+}
+
+
+/// Picks the first supported platform of the ROM, in the database's order of preference.
+fn rom_info(title: &str, rom: &Value) -> Option<RomInfo> {
+    let (id, platform, mut quirks) = rom.get("platforms")?.as_array()?.iter()
+        .filter_map(Value::as_str)
+        .find_map(|id| platform_from_id(id).map(|(platform, quirks)| (id, platform, quirks)))?;
+
+    if let Some(overrides) = rom.get("quirkyPlatforms").and_then(|q| q.get(id)).and_then(Value::as_object) {
+        for (name, value) in overrides {
+            if let Some(enabled) = value.as_bool() {
+                apply_quirk(&mut quirks, name, enabled);
+            }
+        }
+    }
+
+    let keys = rom.get("keys").and_then(Value::as_object).unwrap_or_default().iter()
+        .filter_map(|(role, key)| Some((role.clone(), key.as_u64().filter(|&k| k < 16)? as u8)))
+        .collect();
+
+    let palette = rom.get("colors").and_then(|c| c.get("pixels")).and_then(Value::as_array).map(|pixels| {
+        let mut palette = crate::display::DEFAULT_PALETTE;
+        for (color, pixel) in palette.iter_mut().zip(pixels) {
+            if let Some(rgb) = pixel.as_str().and_then(parse_color) {
+                *color = rgb;
+            }
+        }
+        palette
+    });
+
+    Some(RomInfo {
+        title: title.to_string(),
+        platform,
+        quirks,
+        tickrate: rom.get("tickrate").and_then(Value::as_u64).and_then(|t| u32::try_from(t).ok()).filter(|&t| t > 0),
+        keys,
+        palette,
+    })
+}
+
+
+pub(crate) fn parse_hash(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+
+/// `#RRGGBB` to 0xRRGGBB
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+
+/// SHA-1 digest of `data`.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // pad with a 1 bit, zeros and the length in bits up to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for t in 0..16 {
+            w[t] = u32::from_be_bytes(block[t * 4..t * 4 + 4].try_into().unwrap());
+        }
+        for t in 16..80 {
+            w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (t, word) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn sha1_test() {
+        assert_eq!(sha1(b""), parse_hash("da39a3ee5e6b4b0d3255bfef95601890afd80709").unwrap());
+        assert_eq!(sha1(b"abc"), parse_hash("a9993e364706816aba3e25717850c26c9cd0d89d").unwrap());
+        assert_eq!(
+            sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            parse_hash("84983e441c3bd26ebaae4aa1f95129e5e54670f1").unwrap()
+        );
+    }
+
+    #[test]
+    pub fn parse_test() {
+        let db = RomDatabase::parse(r##"[
+            {"title": "Game", "roms": {
+                "a9993e364706816aba3e25717850c26c9cd0d89d": {
+                    "platforms": ["megachip8", "superchip"],
+                    "quirkyPlatforms": {"superchip": {"wrap": true, "shift": false}},
+                    "tickrate": 30,
+                    "keys": {"left": 4, "right": 6},
+                    "colors": {"pixels": ["#102030", "#FFEEDD"]}
+                },
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709": {"platforms": ["megachip8"]}
+            }}
+        ]"##).unwrap();
+
+        let info = db.lookup(b"abc").unwrap();
+        assert_eq!(info.title, "Game");
+        assert_eq!(info.platform, Platform::SuperChip);
+        assert_eq!(info.quirks, Quirks { clip: false, shift: false, ..Quirks::schip() });
+        assert_eq!(info.tickrate, Some(30));
+        for tickrate in ["0", "4294967296"] {
+            let text = format!(r#"[{{"title": "Game", "roms": {{"a9993e364706816aba3e25717850c26c9cd0d89d": {{"platforms": ["originalChip8"], "tickrate": {}}}}}}}]"#, tickrate);
+            assert_eq!(RomDatabase::parse(&text).unwrap().lookup(b"abc").unwrap().tickrate, None);
+        }
+        assert_eq!(info.keys, vec![("left".to_string(), 4), ("right".to_string(), 6)]);
+        assert_eq!(info.palette, Some([0x102030, 0xFFEEDD, 0xAAAAAA, 0x555555]));
+
+        // MegaChip only
+        assert!(db.lookup(b"").is_none());
+        assert!(RomDatabase::parse(r#"[{"roms": {"abc": {}}}]"#).is_err());
+    }
+
+    #[test]
+    pub fn bundled_test() {
+        let db = RomDatabase::bundled();
+        let ibm_logo = std::fs::read("games/ibm_logo.ch8").unwrap();
+        assert_eq!(db.lookup(&ibm_logo).unwrap().platform, Platform::Chip8);
+        let rockto = std::fs::read("games/rockto.ch8").unwrap();
+        assert_eq!(db.lookup(&rockto).unwrap().platform, Platform::SuperChip);
+    }
+}
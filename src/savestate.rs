@@ -0,0 +1,362 @@
+//! Snapshots of the whole machine in a compact, versioned binary format.
+//!
+//! A state starts with `C8ST` and a version byte, followed by the platform and quirks,
+//! the registers and timers, memory and the framebuffer, both run-length encoded with
+//! PackBits, then the flags and stack. The RNG is saved as the seed continuing its
+//! sequence. The keypad and the error policies are not saved: they belong to the
+//! frontend.
+
+use crate::{Chip8, Chip8Error, KeyWait, Platform, Quirks, Stack};
+use crate::chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::quirks::MemoryIncrement;
+use crate::stack::MAX_STACK_DEPTH;
+
+
+pub const MAGIC: &[u8; 4] = b"C8ST";
+pub const VERSION: u8 = 2;
+pub const SLOTS: u8 = 10; // slots 0 to 9 of the frontend
+
+
+/// File of save slot `slot` for the ROM at `rom_path`: `games/maze.ch8` gives `games/maze.state3`.
+pub fn slot_path(rom_path: &str, slot: u8) -> String {
+    let stem = match rom_path.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => stem,
+        _ => rom_path,
+    };
+    format!("{}.state{}", stem, slot)
+}
+
+
+impl Chip8 {
+    pub fn save_state(&self) -> Vec<u8> {
+        self.encode_state(pack_bits)
+    }
+
+    /// Same layout as `save_state`, but memory and the framebuffer are stored as
+    /// fixed-size literal runs, so that two snapshots line up byte for byte.
+    pub(crate) fn snapshot(&self) -> Vec<u8> {
+        self.encode_state(pack_literals)
+    }
+
+    fn encode_state(&self, pack: fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+
+        out.push(self.platform as u8);
+        let q = &self.quirks;
+        out.push(flags(&[q.shift, q.jump, q.vf_reset, q.clip, q.display_wait]));
+        out.push(q.memory_increment as u8);
+
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.i.to_le_bytes());
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.rpl);
+        out.extend_from_slice(&self.delay_timer.to_le_bytes());
+        out.extend_from_slice(&self.sound_timer.to_le_bytes());
+        out.extend_from_slice(&self.font_location.to_le_bytes());
+        out.extend_from_slice(&self.big_font_location.to_le_bytes());
+        out.push(self.planes);
+        out.push(self.pitch);
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+
+        let memory = pack(&self.memory);
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&memory);
+        let display = pack(self.display_buf.as_flattened());
+        out.extend_from_slice(&(display.len() as u32).to_le_bytes());
+        out.extend_from_slice(&display);
+
+        out.push(flags(&[
+            self.hires, self.halted, self.draw_flag, self.vblank, self.timers_dec_flag,
+            self.trapped.is_some(), self.audio_pattern.is_some(), self.key_wait.is_some(),
+        ]));
+        if let Some(addr) = self.trapped {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        if let Some(pattern) = self.audio_pattern {
+            out.extend_from_slice(&pattern);
+        }
+        if let Some(wait) = self.key_wait {
+            out.push(wait.register as u8);
+            out.push(wait.pressed.unwrap_or(0xFF));
+        }
+
+        out.push(self.stack.depth() as u8);
+        out.push(self.stack.memory_top.is_some() as u8);
+        out.extend_from_slice(&self.stack.memory_top.unwrap_or(0).to_le_bytes());
+        out.push(self.stack.len() as u8);
+        for addr in self.stack.entries() {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+
+        out
+    }
+
+    /// Restores a state made by `save_state`. The machine is left untouched if `data` is
+    /// not a valid state.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut r = Reader { data, pos: 0 };
+        if r.bytes(4)? != MAGIC {
+            return Err(Chip8Error::InvalidState("not a save state"));
+        }
+        if r.u8()? != VERSION {
+            return Err(Chip8Error::InvalidState("saved by an incompatible version"));
+        }
+
+        let mut state = self.clone();
+        state.platform = *Platform::ALL.get(r.u8()? as usize).ok_or(Chip8Error::InvalidState("unknown platform"))?;
+        let [shift, jump, vf_reset, clip, display_wait, ..] = unflags(r.u8()?);
+        let memory_increment = match r.u8()? {
+            0 => MemoryIncrement::XPlusOne,
+            1 => MemoryIncrement::X,
+            2 => MemoryIncrement::None,
+            _ => return Err(Chip8Error::InvalidState("unknown memory increment quirk")),
+        };
+        state.quirks = Quirks { shift, memory_increment, jump, vf_reset, clip, display_wait };
+
+        state.pc = r.u16()?;
+        state.i = r.u16()?;
+        state.v.copy_from_slice(r.bytes(16)?);
+        state.rpl.copy_from_slice(r.bytes(16)?);
+        state.delay_timer = r.u32()?;
+        state.sound_timer = r.u32()?;
+        state.font_location = r.u16()?;
+        state.big_font_location = r.u16()?;
+        state.planes = r.u8()?;
+        state.pitch = r.u8()?;
+        state.rng.seed(r.u64()?);
+
+        let size = r.u32()? as usize;
+        if size != state.platform.memory_size() {
+            return Err(Chip8Error::InvalidState("memory size does not match the platform"));
+        }
+        let len = r.u32()? as usize;
+        state.memory = unpack_bits(r.bytes(len)?, size)?;
+        let len = r.u32()? as usize;
+        let display = unpack_bits(r.bytes(len)?, DISPLAY_WIDTH * DISPLAY_HEIGHT)?;
+        for (column, pixels) in state.display_buf.iter_mut().zip(display.chunks(DISPLAY_HEIGHT)) {
+            column.copy_from_slice(pixels);
+        }
+
+        let [hires, halted, draw_flag, vblank, timers_dec_flag, trapped, audio_pattern, key_wait] = unflags(r.u8()?);
+        (state.hires, state.halted, state.draw_flag, state.vblank, state.timers_dec_flag) = (hires, halted, draw_flag, vblank, timers_dec_flag);
+        state.trapped = if trapped { Some(r.u16()?) } else { None };
+        state.audio_pattern = if audio_pattern { Some(r.bytes(16)?.try_into().unwrap()) } else { None };
+        state.key_wait = if key_wait {
+            let register = r.u8()? as usize & 0xF;
+            let pressed = Some(r.u8()?).filter(|&key| key < 16);
+            Some(KeyWait { register, pressed })
+        } else {
+            None
+        };
+
+        let depth = r.u8()? as usize;
+        if depth > MAX_STACK_DEPTH {
+            return Err(Chip8Error::InvalidState("stack too deep"));
+        }
+        let has_top = r.u8()? != 0;
+        let top = r.u16()?;
+        let mut stack = Stack::new(depth);
+        stack.memory_top = has_top.then_some(top);
+        for _ in 0..r.u8()? {
+            if !stack.push(r.u16()?) {
+                return Err(Chip8Error::InvalidState("stack overflow"));
+            }
+        }
+        state.stack = stack;
+
+        if r.pos != data.len() {
+            return Err(Chip8Error::InvalidState("trailing data"));
+        }
+
+        *self = state;
+        Ok(())
+    }
+}
+
+
+fn flags(bits: &[bool]) -> u8 {
+    bits.iter().enumerate().fold(0, |acc, (k, &bit)| acc | (bit as u8) << k)
+}
+
+
+fn unflags(byte: u8) -> [bool; 8] {
+    std::array::from_fn(|k| byte & (1 << k) != 0)
+}
+
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or(Chip8Error::InvalidState("truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+
+/// PackBits run-length encoding: a header byte `n` is followed by `n + 1` literal bytes
+/// if n < 128, or by one byte repeated `257 - n` times otherwise.
+fn pack_bits(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let run = data[pos..].iter().take(128).take_while(|&&b| b == data[pos]).count();
+        if run >= 3 {
+            out.push((257 - run) as u8);
+            out.push(data[pos]);
+            pos += run;
+            continue;
+        }
+        // literals, up to the next run of 3
+        let start = pos;
+        while pos < data.len() && pos - start < 128 {
+            if pos + 2 < data.len() && data[pos] == data[pos + 1] && data[pos] == data[pos + 2] {
+                break;
+            }
+            pos += 1;
+        }
+        out.push((pos - start - 1) as u8);
+        out.extend_from_slice(&data[start..pos]);
+    }
+    out
+}
+
+
+/// PackBits without runs: `data` split in literals of 128 bytes.
+fn pack_literals(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len().div_ceil(128));
+    for chunk in data.chunks(128) {
+        out.push(chunk.len() as u8 - 1);
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+
+/// Decodes `pack_bits` or `pack_literals` output, which must expand to exactly `len` bytes.
+fn unpack_bits(data: &[u8], len: usize) -> Result<Vec<u8>, Chip8Error> {
+    let corrupt = || Chip8Error::InvalidState("corrupt run-length data");
+    let mut out = Vec::with_capacity(len);
+    let mut bytes = data.iter();
+    while let Some(&header) = bytes.next() {
+        match header {
+            0..=127 => {
+                for _ in 0..=header {
+                    out.push(*bytes.next().ok_or_else(corrupt)?);
+                }
+            }
+            128 => {}
+            _ => {
+                let byte = *bytes.next().ok_or_else(corrupt)?;
+                out.extend(std::iter::repeat_n(byte, 257 - header as usize));
+            }
+        }
+        if out.len() > len {
+            return Err(corrupt());
+        }
+    }
+    if out.len() != len {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn pack_bits_test() {
+        for data in [vec![], vec![7], vec![0; 4096], vec![1, 2, 2, 3, 3, 3, 3, 4], (0..=255).cycle().take(1000).collect()] {
+            let packed = pack_bits(&data);
+            assert_eq!(unpack_bits(&packed, data.len()).unwrap(), data);
+        }
+        assert_eq!(pack_bits(&[0; 4096]).len(), 64);
+        let data: Vec<u8> = (0..300).map(|k| k as u8).collect();
+        assert_eq!(pack_literals(&data).len(), 303);
+        assert_eq!(unpack_bits(&pack_literals(&data), 300).unwrap(), data);
+        assert!(unpack_bits(&[0x05, 1, 2], 6).is_err());
+        assert!(unpack_bits(&[0xFE, 1], 2).is_err());
+    }
+
+    #[test]
+    pub fn save_state_test() {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.memory[0x200..0x204].copy_from_slice(&[0x60, 0x2A, 0x22, 0x08]);
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        chip8.display_buf[3][4] = 0b11;
+        chip8.hires = true;
+        chip8.delay_timer = 40;
+        chip8.quirks.clip = true;
+        chip8.audio_pattern = Some([0xAA; 16]);
+        chip8.key_wait = Some(KeyWait { register: 3, pressed: Some(0xB) });
+
+        let state = chip8.save_state();
+        assert!(state.len() < 2048, "{} bytes", state.len()); // 72KB of memory and screen unpacked
+
+        let mut other = Chip8::init();
+        other.load_state(&state).unwrap();
+        assert_eq!(other.platform, Platform::XoChip);
+        assert_eq!(other.quirks, chip8.quirks);
+        assert_eq!(other.memory, chip8.memory);
+        assert_eq!((other.pc, other.v[0], other.delay_timer), (0x208, 0x2A, 40));
+        assert_eq!(other.stack, chip8.stack);
+        assert_eq!(other.display_buf, chip8.display_buf);
+        assert!(other.hires);
+        assert_eq!(other.audio_pattern, chip8.audio_pattern);
+        assert_eq!(other.key_wait, chip8.key_wait);
+        assert_eq!(other.save_state(), state);
+
+        let mut other = Chip8::init();
+        other.load_state(&chip8.snapshot()).unwrap();
+        assert_eq!(other.save_state(), state);
+
+        // the random sequence goes on where it was saved
+        chip8.seed_rng(5);
+        chip8.rng.next_byte();
+        other.load_state(&chip8.save_state()).unwrap();
+        assert_eq!(other.rng.next_byte(), chip8.rng.next_byte());
+    }
+
+    #[test]
+    pub fn invalid_state_test() {
+        let mut chip8 = Chip8::init();
+        chip8.v[0] = 1;
+        let mut state = chip8.save_state();
+        chip8.v[0] = 2;
+
+        assert!(matches!(chip8.load_state(b"nope"), Err(Chip8Error::InvalidState(_))));
+        assert!(chip8.load_state(&state[..state.len() - 1]).is_err());
+        state[4] = VERSION + 1;
+        assert!(chip8.load_state(&state).is_err());
+        assert_eq!(chip8.v[0], 2); // untouched
+
+        assert_eq!(slot_path("games/maze.ch8", 3), "games/maze.state3");
+        assert_eq!(slot_path("./roms/pong", 0), "./roms/pong.state0");
+    }
+}
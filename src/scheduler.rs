@@ -0,0 +1,88 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1); // below this, yield instead of sleeping
+
+
+/// Paces frames at a fixed rate against the wall clock.
+///
+/// Frames that are late are run back-to-back to catch up, up to `max_catch_up` at once;
+/// beyond that the schedule is reset and the missed frames are dropped.
+pub struct FrameScheduler {
+    frame_duration: Duration,
+    next_frame: Instant,
+    pub max_catch_up: u32,
+    pub dropped: u64, // frames dropped so far
+}
+
+
+impl FrameScheduler {
+    pub fn new(frame_rate: u32, now: Instant) -> FrameScheduler {
+        FrameScheduler {
+            frame_duration: Duration::from_secs(1) / frame_rate,
+            next_frame: now,
+            max_catch_up: 5,
+            dropped: 0,
+        }
+    }
+
+    /// Number of frames due at `now`, 0 if the next one is not due yet.
+    pub fn due_frames(&mut self, now: Instant) -> u32 {
+        if now < self.next_frame {
+            return 0;
+        }
+        let late = ((now - self.next_frame).as_nanos() / self.frame_duration.as_nanos()) as u64 + 1;
+        if late > self.max_catch_up as u64 {
+            self.dropped += late - self.max_catch_up as u64;
+            self.next_frame = now + self.frame_duration;
+            return self.max_catch_up;
+        }
+        self.next_frame += self.frame_duration * late as u32;
+        late as u32
+    }
+
+    /// Blocks until at least one frame is due and returns how many are.
+    pub fn wait(&mut self) -> u32 {
+        loop {
+            let now = Instant::now();
+            let due = self.due_frames(now);
+            if due > 0 {
+                return due;
+            }
+            // sleep() overshoots by up to the OS timer granularity, finish off by yielding
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_THRESHOLD {
+                thread::sleep(remaining - SPIN_THRESHOLD);
+            } else {
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn due_frames_test() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(50, start); // 20ms frames
+        let ms = |n| start + Duration::from_millis(n);
+
+        assert_eq!(scheduler.due_frames(ms(0)), 1);
+        assert_eq!(scheduler.due_frames(ms(10)), 0);
+        assert_eq!(scheduler.due_frames(ms(20)), 1);
+        // the frames at 40 and 60ms are late: catch up
+        assert_eq!(scheduler.due_frames(ms(79)), 2);
+        assert_eq!(scheduler.due_frames(ms(80)), 1);
+
+        // far behind: run max_catch_up frames and drop the rest
+        assert_eq!(scheduler.due_frames(ms(300)), 5);
+        assert_eq!(scheduler.dropped, 6);
+        assert_eq!(scheduler.due_frames(ms(310)), 0);
+        assert_eq!(scheduler.due_frames(ms(320)), 1);
+    }
+}
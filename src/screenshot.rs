@@ -0,0 +1,159 @@
+//! Framebuffer snapshots of the visible screen as PBM, PNG or ASCII art.
+
+use crate::Chip8;
+
+
+/// Portable bitmap (binary P4): lit pixels, on any bitplane, are 1s (black).
+pub fn to_pbm(chip8: &Chip8) -> Vec<u8> {
+    let (width, height) = (chip8.width(), chip8.height());
+    let mut out = format!("P4\n{} {}\n", width, height).into_bytes();
+    for y in 0..height {
+        for byte in 0..width.div_ceil(8) {
+            let mut bits = 0;
+            for bit in 0..8 {
+                let x = byte * 8 + bit;
+                if x < width && chip8.pixel(x, y) {
+                    bits |= 0x80 >> bit;
+                }
+            }
+            out.push(bits);
+        }
+    }
+    out
+}
+
+
+/// One character per pixel: `.` when off, `#` on plane 1, `+` on plane 2 and `@` on both.
+pub fn to_ascii(chip8: &Chip8) -> String {
+    let mut out = String::with_capacity((chip8.width() + 1) * chip8.height());
+    for y in 0..chip8.height() {
+        for x in 0..chip8.width() {
+            out.push(['.', '#', '+', '@'][chip8.display_buf[x][y] as usize & 0b11]);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+
+/// 8-bit RGB PNG, pixel values coloured with `palette` (0xRRGGBB).
+pub fn to_png(chip8: &Chip8, palette: &[u32; 4]) -> Vec<u8> {
+    let (width, height) = (chip8.width(), chip8.height());
+
+    // scanlines, each prefixed by filter type 0 (none)
+    let mut raw = Vec::with_capacity(height * (1 + width * 3));
+    for y in 0..height {
+        raw.push(0);
+        for x in 0..width {
+            let rgb = palette[chip8.display_buf[x][y] as usize & 0b11];
+            raw.extend_from_slice(&rgb.to_be_bytes()[1..]);
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8 bits per channel, RGB, deflate, no filter, no interlace
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+
+/// zlib stream of uncompressed deflate blocks: screens are a few KB at most.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::DEFAULT_PALETTE;
+
+    fn test_screen() -> Chip8 {
+        let mut chip8 = Chip8::init();
+        chip8.display_buf[0][0] = 1;
+        chip8.display_buf[9][1] = 1;
+        chip8
+    }
+
+    #[test]
+    pub fn pbm_test() {
+        let pbm = to_pbm(&test_screen());
+        let header = b"P4\n64 32\n";
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(pbm.len(), header.len() + 8 * 32);
+        assert_eq!(pbm[header.len()], 0x80);
+        assert_eq!(pbm[header.len() + 8 + 1], 0x40);
+    }
+
+    #[test]
+    pub fn ascii_test() {
+        let ascii = to_ascii(&test_screen());
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(&lines[0][..3], "#..");
+        assert_eq!(&lines[1][8..11], ".#.");
+    }
+
+    #[test]
+    pub fn png_test() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let png = to_png(&test_screen(), &DEFAULT_PALETTE);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+}
@@ -0,0 +1,111 @@
+use std::io;
+use chip8emu::{Chip8, Movie};
+
+
+/// Whether the frames come from the player or from a movie, and where they are recorded.
+pub enum Session {
+    Live,
+    Record { movie: Movie, path: String },
+    Play { movie: Movie, frame: usize },
+}
+
+
+impl Session {
+    /// Sets up the keypad for the next frame and returns the instructions to run in it.
+    pub fn before_frame(&mut self, chip8: &mut Chip8, ipf: u32) -> u32 {
+        match self {
+            Session::Play { movie, frame } => movie.apply(*frame, chip8),
+            _ => ipf,
+        }
+    }
+
+    /// Records or checks the frame just run. Returns a message when a playback ends.
+    pub fn after_frame(&mut self, chip8: &Chip8, ipf: u32) -> Option<String> {
+        match self {
+            Session::Live => None,
+            Session::Record { movie, .. } => {
+                movie.record(chip8, ipf);
+                None
+            }
+            Session::Play { movie, frame } => {
+                let message = if !movie.check(*frame, chip8) {
+                    format!("desync at frame {}", frame)
+                } else if *frame + 1 == movie.frames.len() {
+                    "movie ended".to_string()
+                } else {
+                    *frame += 1;
+                    return None;
+                };
+                *self = Session::Live;
+                Some(message)
+            }
+        }
+    }
+
+    /// Forgets the last recorded frame, after the game was rewound by one frame.
+    pub fn rewound(&mut self) {
+        if let Session::Record { movie, .. } = self {
+            movie.frames.pop();
+        }
+    }
+
+    /// true unless playing: rewinding or loading a state would desync the movie.
+    pub fn allows_time_travel(&self) -> bool {
+        !matches!(self, Session::Play { .. })
+    }
+
+    /// true unless recording or playing: a loaded state cannot be replayed from the
+    /// ROM's start.
+    pub fn allows_loading(&self) -> bool {
+        matches!(self, Session::Live)
+    }
+
+    pub fn status(&self) -> String {
+        match self {
+            Session::Live => String::new(),
+            Session::Record { movie, .. } => format!("  rec {}", movie.frames.len()),
+            Session::Play { movie, frame } => format!("  play {}/{}", frame, movie.frames.len()),
+        }
+    }
+
+    /// Writes the movie being recorded.
+    pub fn finish(&self) -> io::Result<()> {
+        match self {
+            Session::Record { movie, path } => std::fs::write(path, movie.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn session_test() {
+        let mut chip8 = Chip8::init();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+        let mut movie = Movie::new(&chip8, &[0x12, 0x00], 7, 10);
+        chip8.keypad.press(3);
+        for _ in 0..2 {
+            chip8.run_frame(10).unwrap();
+            movie.record(&chip8, 10);
+        }
+
+        chip8.keypad.release(3);
+        let mut session = Session::Play { movie, frame: 0 };
+        assert_eq!(session.before_frame(&mut chip8, 12), 10);
+        assert!(chip8.keypad.is_pressed(3));
+        assert_eq!(session.after_frame(&chip8, 10), None);
+        assert_eq!(session.status(), "  play 1/2");
+        assert!(!session.allows_time_travel());
+        assert!(!session.allows_loading());
+
+        // the screen changes behind the movie's back
+        chip8.display_buf[0][0] = 1;
+        assert_eq!(session.after_frame(&chip8, 10), Some("desync at frame 1".to_string()));
+        assert!(matches!(session, Session::Live));
+        assert!(session.allows_loading());
+    }
+}
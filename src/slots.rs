@@ -0,0 +1,99 @@
+use chip8emu::Chip8;
+use chip8emu::savestate::{self, SLOTS};
+
+
+/// Numbered save state files next to the ROM, and the one selected for the hotkeys.
+pub struct SaveSlots {
+    rom_path: String,
+    pub slot: u8,
+    message: String, // outcome of the last save or load
+}
+
+
+impl SaveSlots {
+    pub fn new(rom_path: &str) -> SaveSlots {
+        SaveSlots { rom_path: rom_path.to_string(), slot: 0, message: String::new() }
+    }
+
+    pub fn next(&mut self) {
+        self.slot = (self.slot + 1) % SLOTS;
+        self.message.clear();
+    }
+
+    pub fn save(&mut self, chip8: &Chip8) {
+        let path = savestate::slot_path(&self.rom_path, self.slot);
+        self.message = match std::fs::write(&path, chip8.save_state()) {
+            Ok(()) => "saved".to_string(),
+            Err(err) => format!("save failed: {}", err),
+        };
+    }
+
+    /// Restores the selected slot, leaving the machine as it is if that fails. States of
+    /// another platform are refused, the display is sized for the current one.
+    pub fn load(&mut self, chip8: &mut Chip8) {
+        let path = savestate::slot_path(&self.rom_path, self.slot);
+        let result = std::fs::read(&path).map_err(|err| err.to_string()).and_then(|data| {
+            let mut loaded = chip8.clone();
+            loaded.load_state(&data).map_err(|err| err.to_string())?;
+            if loaded.platform != chip8.platform {
+                return Err(format!("state of another platform ({})", loaded.platform.name()));
+            }
+            *chip8 = loaded;
+            Ok(())
+        });
+        self.message = match result {
+            Ok(()) => {
+                chip8.draw_flag = true;
+                "loaded".to_string()
+            }
+            Err(err) => format!("load failed: {}", err),
+        };
+    }
+
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    pub fn status(&self) -> String {
+        match self.message.as_str() {
+            "" => format!("slot {}", self.slot),
+            message => format!("slot {} {}", self.slot, message),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8emu::Platform;
+
+    #[test]
+    pub fn slots_test() {
+        let mut slots = SaveSlots::new("games/maze.ch8");
+        assert_eq!(slots.status(), "slot 0");
+        for _ in 0..SLOTS + 3 {
+            slots.next();
+        }
+        assert_eq!(slots.slot, 3);
+
+        let mut chip8 = Chip8::init();
+        slots.rom_path = "/nonexistent/maze.ch8".to_string();
+        slots.load(&mut chip8);
+        assert!(slots.status().starts_with("slot 3 load failed"));
+        assert!(!chip8.draw_flag);
+
+        // states of another platform are refused
+        let dir = std::env::temp_dir().join(format!("chip8emu-slots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        slots.rom_path = dir.join("maze.ch8").to_string_lossy().into_owned();
+        slots.save(&Chip8::with_platform(Platform::XoChip));
+        slots.load(&mut chip8);
+        assert!(slots.status().contains("another platform"));
+        assert_eq!(chip8.platform, Platform::Chip8);
+        slots.save(&chip8);
+        slots.load(&mut chip8);
+        assert_eq!(slots.status(), "slot 3 loaded");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
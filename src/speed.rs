@@ -0,0 +1,104 @@
+//! Runtime speed controls of the terminal frontend.
+
+
+/// Instructions per frame offered by the speed up and down hotkeys.
+const SPEED_STEPS: [u32; 15] = [1, 2, 4, 7, 10, 12, 15, 20, 30, 50, 100, 200, 500, 1000, 2000];
+pub const TURBO_FRAMES: u32 = 8; // frames run per frame in turbo mode
+
+
+pub struct Speed {
+    pub ipf: u32,       // instructions per frame
+    pub paused: bool,
+    pub turbo: bool,
+    advance: bool,      // run a single frame while paused
+}
+
+
+impl Speed {
+    pub fn new(ipf: u32) -> Speed {
+        Speed { ipf: ipf.max(1), paused: false, turbo: false, advance: false }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+    }
+
+    /// Runs the next frame, then pauses.
+    pub fn advance(&mut self) {
+        self.paused = true;
+        self.advance = true;
+    }
+
+    /// Next speed step above the current one.
+    pub fn faster(&mut self) {
+        self.ipf = SPEED_STEPS.iter().copied().find(|&s| s > self.ipf).unwrap_or(self.ipf);
+    }
+
+    /// Next speed step below the current one.
+    pub fn slower(&mut self) {
+        self.ipf = SPEED_STEPS.iter().copied().rev().find(|&s| s < self.ipf).unwrap_or(self.ipf);
+    }
+
+    /// Number of interpreter frames to run for one frame of wall-clock time.
+    pub fn frames_to_run(&mut self) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.advance) as u32;
+        }
+        if self.turbo { TURBO_FRAMES } else { 1 }
+    }
+
+    /// Speed as shown to the user, `timer_hz` being the frame rate.
+    pub fn status(&self, timer_hz: u32) -> String {
+        let mut status = format!("{} ipf ({}/s)", self.ipf, self.ipf * timer_hz);
+        if self.turbo {
+            status += &format!(" turbo x{}", TURBO_FRAMES);
+        }
+        if self.paused {
+            status += " paused";
+        }
+        status
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn speed_steps_test() {
+        let mut speed = Speed::new(12);
+        speed.faster();
+        assert_eq!(speed.ipf, 15);
+        speed.slower();
+        speed.slower();
+        assert_eq!(speed.ipf, 10);
+
+        // off the ladder
+        let mut speed = Speed::new(11);
+        speed.faster();
+        assert_eq!(speed.ipf, 12);
+        let mut speed = Speed::new(1);
+        speed.slower();
+        assert_eq!(speed.ipf, 1);
+    }
+
+    #[test]
+    pub fn frames_to_run_test() {
+        let mut speed = Speed::new(12);
+        assert_eq!(speed.frames_to_run(), 1);
+        speed.toggle_turbo();
+        assert_eq!(speed.frames_to_run(), TURBO_FRAMES);
+
+        speed.toggle_pause();
+        assert_eq!(speed.frames_to_run(), 0);
+        speed.advance();
+        assert_eq!(speed.frames_to_run(), 1);
+        assert_eq!(speed.frames_to_run(), 0);
+        assert_eq!(speed.status(60), "12 ipf (720/s) turbo x8 paused");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{Chip8, Chip8Error};
+
+
+pub const MAX_STACK_DEPTH: usize = 64;
+pub const VIP_STACK_TOP: u16 = 0xED0; // the COSMAC VIP stack grows down from 0xECF
+
+
+/// Call stack of return addresses, with a fixed number of entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stack {
+    entries: [u16; MAX_STACK_DEPTH],
+    len: usize,
+    depth: usize,                // entries before an overflow, at most MAX_STACK_DEPTH
+    pub memory_top: Option<u16>, // if set, entries are also stored big-endian in memory, growing down from there
+}
+
+
+impl Stack {
+    pub fn new(depth: usize) -> Stack {
+        Stack {
+            entries: [0; MAX_STACK_DEPTH],
+            len: 0,
+            depth: depth.min(MAX_STACK_DEPTH),
+            memory_top: None,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Changes the depth, dropping the entries that no longer fit.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth.min(MAX_STACK_DEPTH);
+        self.len = self.len.min(self.depth);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return addresses, the most recent call last.
+    pub fn entries(&self) -> &[u16] {
+        &self.entries[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// false if the stack is full.
+    pub fn push(&mut self, addr: u16) -> bool {
+        if self.len >= self.depth {
+            return false;
+        }
+        self.entries[self.len] = addr;
+        self.len += 1;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<u16> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.entries[self.len])
+    }
+
+    /// Address in memory of entry `index`, when the stack lives in memory.
+    fn memory_slot(&self, index: usize) -> Option<usize> {
+        self.memory_top.map(|top| (top as usize).wrapping_sub(2 * (index + 1)))
+    }
+}
+
+
+impl Chip8 {
+    /// Pushes the return address of the call at `addr`.
+    pub(crate) fn push_return(&mut self, addr: u16, ret: u16) -> Result<(), Chip8Error> {
+        if self.stack.len() >= self.stack.depth() {
+            return Err(Chip8Error::StackOverflow { addr });
+        }
+        // written before pushing, low byte first, so that nothing changes if it fails
+        if let Some(slot) = self.stack.memory_slot(self.stack.len()) {
+            self.write_byte(slot + 1, ret as u8)?;
+            self.write_byte(slot, (ret >> 8) as u8)?;
+        }
+        self.stack.push(ret);
+        Ok(())
+    }
+
+    /// Pops the return address for the return at `addr`. When the stack lives in memory,
+    /// the address is read back from there, so that ROMs can rewrite it.
+    pub(crate) fn pop_return(&mut self, addr: u16) -> Result<u16, Chip8Error> {
+        let ret = self.stack.pop().ok_or(Chip8Error::StackUnderflow { addr })?;
+        match self.stack.memory_slot(self.stack.len()) {
+            Some(slot) => Ok(((self.read_byte(slot)? as u16) << 8) | self.read_byte(slot + 1)? as u16),
+            None => Ok(ret),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn stack_test() {
+        let mut stack = Stack::new(2);
+        assert!(stack.push(0x202) && stack.push(0x304));
+        assert!(!stack.push(0x406));
+        assert_eq!(stack.entries(), &[0x202, 0x304]);
+        assert_eq!(stack.pop(), Some(0x304));
+        assert_eq!(stack.pop(), Some(0x202));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    pub fn memory_stack_test() {
+        let mut chip8 = Chip8::init();
+        chip8.stack.memory_top = Some(VIP_STACK_TOP);
+        chip8.push_return(0x200, 0x202).unwrap();
+        chip8.push_return(0x300, 0x302).unwrap();
+        assert_eq!(&chip8.memory[0xECC..0xED0], &[0x03, 0x02, 0x02, 0x02]);
+
+        // the ROM rewrites the return address
+        chip8.memory[0xECD] = 0x10;
+        assert_eq!(chip8.pop_return(0x400).unwrap(), 0x310);
+        assert_eq!(chip8.pop_return(0x400).unwrap(), 0x202);
+        assert!(matches!(chip8.pop_return(0x400), Err(Chip8Error::StackUnderflow { addr: 0x400 })));
+
+        // a stack past the end of memory fails without pushing
+        chip8.memory_policy = crate::MemoryPolicy::Error;
+        chip8.stack.memory_top = Some(0x1001);
+        assert!(chip8.push_return(0x200, 0x202).is_err());
+        assert!(chip8.stack.is_empty());
+        assert_eq!(chip8.memory[0xFFF], 0);
+    }
+}
@@ -0,0 +1,161 @@
+use chip8emu::{Chip8, Platform};
+use chip8emu::display::DEFAULT_PALETTE;
+use std::io::{stdout, Write};
+use termkan::{rds::Renderer, math::*, img::Color};
+
+
+const KEY_WAIT_BORDER: Color = Color::GOLD;
+const TRAPPED_BORDER: Color = Color::RED;
+const PANEL_WIDTH: i32 = 48; // columns needed to show the debugger panel right of the screen
+
+
+pub struct Display {
+    top_left: Vec2,
+    size: Vec2, // screen size in terminal pixels, the largest resolution of the platform
+    palette: [Color; 4],
+    key_wait: bool,
+    trapped: bool,
+    status: String,
+    panel: Vec<String>, // debugger panel lines, as printed
+}
+
+
+impl Display {
+    /// Centres the screen, or docks it on the left to leave room for the debugger panel.
+    pub fn new(platform: Platform, dock_left: bool) -> Display {
+        let rds = Renderer::get();
+        let dim = Renderer::get_size();
+        let (width, height) = platform.max_resolution();
+        let size = Vec2::new(width as i32, height as i32);
+
+        let top_left = Vec2 {
+            x: if dock_left { 0 } else { (dim.x / 2) - size.x / 2 - 1 },
+            y: (dim.y / 2) - size.y / 2 - 1,
+        };
+
+        rds.begin_draw();
+        rds.draw_rect(top_left, size + Vec2::new(2, 2), Color::WHITE);
+        rds.end_draw();
+
+        Display {
+            top_left: Vec2 {
+                x: top_left.x + 1,
+                y: top_left.y + 1
+            },
+            size,
+            palette: DEFAULT_PALETTE.map(Color::hex),
+            key_wait: false,
+            trapped: false,
+            status: String::new(),
+            panel: Vec::new(),
+        }
+    }
+
+    /// Replaces the colours of the four pixel values (0xRRGGBB).
+    pub fn set_palette(&mut self, palette: [u32; 4]) {
+        self.palette = palette.map(Color::hex);
+    }
+
+    /// Restores the terminal and exits the process.
+    pub fn close(self) {
+        Renderer::exit();
+    }
+
+    /// Highlights the border while the interpreter is blocked on FX0A.
+    pub fn set_key_wait(&mut self, waiting: bool) {
+        if waiting != self.key_wait {
+            self.key_wait = waiting;
+            self.draw_border();
+        }
+    }
+
+    /// Highlights the border while the interpreter is stopped on a trapping instruction.
+    pub fn set_trapped(&mut self, trapped: bool) {
+        if trapped != self.trapped {
+            self.trapped = trapped;
+            self.draw_border();
+        }
+    }
+
+    /// Shows `status` on the line below the screen.
+    pub fn set_status(&mut self, status: &str) {
+        if status == self.status {
+            return;
+        }
+        // the renderer thread is idle between begin_draw and end_draw, and never redraws
+        // the cells outside of the screen, so the text can be printed directly
+        let row = (self.top_left.y + self.size.y) / 2 + 2;
+        let col = self.top_left.x + 1;
+        let rds = Renderer::get();
+        rds.begin_draw();
+        // save and restore the cursor and colours the renderer relies on
+        print!("\x1b7\x1b[{};{}H\x1b[0m{:<width$}\x1b8", row, col, status, width = self.status.len().max(status.len()));
+        let _ = stdout().flush();
+        rds.end_draw();
+        self.status = status.to_string();
+    }
+
+    /// Shows the debugger panel right of the screen, or below the status line when the
+    /// terminal is too narrow. Only the lines that changed are printed again.
+    pub fn set_panel(&mut self, lines: &[String]) {
+        let dim = Renderer::get_size();
+        let right = self.top_left.x + self.size.x + 4;
+        let (row, col) = if right + PANEL_WIDTH <= dim.x + 1 {
+            ((self.top_left.y - 1) / 2 + 1, right)
+        } else {
+            ((self.top_left.y + self.size.y) / 2 + 4, self.top_left.x)
+        };
+        let width = (dim.x + 1 - col).max(0) as usize;
+
+        let rds = Renderer::get();
+        rds.begin_draw();
+        for k in 0..lines.len().max(self.panel.len()) {
+            let old = self.panel.get(k).map_or("", String::as_str);
+            let new: String = lines.get(k).map_or("", String::as_str).chars().take(width).collect();
+            if new != old {
+                print!("\x1b7\x1b[{};{}H\x1b[0m{:<pad$}\x1b8", row + k as i32, col, new, pad = old.len().max(new.len()));
+            }
+        }
+        let _ = stdout().flush();
+        rds.end_draw();
+        self.panel = lines.iter().map(|line| line.chars().take(width).collect()).collect();
+    }
+
+    fn draw_border(&self) {
+        let color = if self.trapped {
+            TRAPPED_BORDER
+        } else if self.key_wait {
+            KEY_WAIT_BORDER
+        } else {
+            Color::WHITE
+        };
+        let rds = Renderer::get();
+        rds.begin_draw();
+        rds.draw_rect_boundary(self.top_left - Vec2::new(1, 1), self.size + Vec2::new(1, 1), color);
+        rds.end_draw();
+    }
+
+    pub fn draw_point(&self, vec2: Vec2, color: Color) {
+        let rds = Renderer::get();
+        let coords = Vec2 {
+            x: self.top_left.x + vec2.x,
+            y: self.top_left.y + vec2.y,
+        };
+
+        rds.draw_point(coords, color);
+    }
+
+    /// Draws the visible part of `display_buf`, scaled up to fill the screen in low resolution.
+    pub fn update(&mut self, chip8: &Chip8) {
+        let scale = self.size.x / chip8.width() as i32;
+        let rds = Renderer::get();
+        rds.begin_draw();
+        for x in 0..self.size.x {
+            for y in 0..self.size.y {
+                let pixel = chip8.display_buf[(x / scale) as usize][(y / scale) as usize];
+                self.draw_point(Vec2::new(x, y), self.palette[pixel as usize & 0b11]);
+            }
+        }
+        rds.end_draw();
+    }
+}
\ No newline at end of file
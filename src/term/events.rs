@@ -0,0 +1,231 @@
+use std::io::{stdin, stdout, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+use termkan::input::KeyEvent;
+
+
+// kitty keyboard protocol: disambiguate (1) + report event types (2) + report all keys as escape codes (8)
+const KITTY_PUSH_FLAGS: &str = "\x1b[>11u";
+const KITTY_POP_FLAGS: &str = "\x1b[<u";
+const KITTY_QUERY_FLAGS: &str = "\x1b[?u";
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Press,
+    Repeat,
+    Release,
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TermEvent {
+    Key(KeyEvent, KeyAction),
+    KittySupported, // the terminal answered the kitty protocol query: releases will be reported
+}
+
+
+/// Reads and parses stdin on its own thread.
+///
+/// termkan's `Input` drops the `CSI ... u` sequences of the kitty keyboard protocol, so
+/// the terminal frontend does its own parsing. Legacy terminals ignore the protocol
+/// escape codes and keep sending plain bytes, which are parsed as presses.
+pub struct EventReader {
+    recv: mpsc::Receiver<TermEvent>,
+}
+
+
+impl EventReader {
+    pub fn start() -> EventReader {
+        print!("{}{}", KITTY_PUSH_FLAGS, KITTY_QUERY_FLAGS);
+        stdout().flush().expect("Could not write to stdout");
+
+        let (send, recv) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = stdin();
+            let mut buf = [0u8; 256];
+            loop {
+                let len = match stdin.read(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(len) => len,
+                };
+                for event in parse_events(&buf[..len]) {
+                    if send.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        EventReader { recv }
+    }
+
+    /// Returns the next pending event without blocking.
+    pub fn poll(&self) -> Option<TermEvent> {
+        self.recv.try_recv().ok()
+    }
+}
+
+
+impl Drop for EventReader {
+    fn drop(&mut self) {
+        print!("{}", KITTY_POP_FLAGS);
+        stdout().flush().expect("Could not write to stdout");
+    }
+}
+
+
+/// Parses every event contained in one read from the terminal.
+pub fn parse_events(bytes: &[u8]) -> Vec<TermEvent> {
+    let mut events = Vec::new();
+    let mut k = 0;
+    while k < bytes.len() {
+        let (event, len) = parse_event(&bytes[k..]);
+        if let Some(event) = event {
+            events.push(event);
+        }
+        k += len;
+    }
+    events
+}
+
+
+/// Parses one event at the start of `bytes`, returning it with the number of bytes consumed.
+fn parse_event(bytes: &[u8]) -> (Option<TermEvent>, usize) {
+    let press = |key| Some(TermEvent::Key(key, KeyAction::Press));
+    match bytes[0] {
+        b'\x1B' => match bytes.get(1) {
+            None => (press(KeyEvent::Esc), 1),
+            Some(b'[') => parse_csi(&bytes[2..]),
+            Some(b'O') => match bytes.get(2) {
+                Some(&c @ b'P'..=b'S') => (press(KeyEvent::F(1 + c - b'P')), 3),
+                _ => (None, 2),
+            },
+            Some(_) => {
+                let (ch, len) = parse_utf8_char(&bytes[1..]);
+                (ch.map(|c| TermEvent::Key(KeyEvent::Alt(c), KeyAction::Press)), 1 + len)
+            }
+        },
+        b'\n' | b'\r' => (press(KeyEvent::Char('\n')), 1),
+        b'\t' => (press(KeyEvent::Char('\t')), 1),
+        b'\x7F' => (press(KeyEvent::Backspace), 1),
+        c @ b'\x01'..=b'\x1A' => (press(KeyEvent::Ctrl((c - 0x1 + b'a') as char)), 1),
+        c @ b'\x1C'..=b'\x1F' => (press(KeyEvent::Ctrl((c - 0x1C + b'4') as char)), 1),
+        b'\0' => (press(KeyEvent::Null), 1),
+        _ => {
+            let (ch, len) = parse_utf8_char(bytes);
+            (ch.map(|c| TermEvent::Key(KeyEvent::Char(c), KeyAction::Press)), len)
+        }
+    }
+}
+
+
+/// Parses a CSI sequence, `bytes` starting just after `ESC [`.
+fn parse_csi(bytes: &[u8]) -> (Option<TermEvent>, usize) {
+    // parameters, then a final byte in 0x40..=0x7E
+    let end = match bytes.iter().position(|&b| (0x40..=0x7E).contains(&b)) {
+        Some(end) => end,
+        None => return (None, 2 + bytes.len()),
+    };
+    let params = std::str::from_utf8(&bytes[..end]).unwrap_or("");
+    let len = 2 + end + 1;
+
+    if let Some(flags) = params.strip_prefix('?') {
+        if bytes[end] == b'u' && flags.parse::<u32>().is_ok() {
+            return (Some(TermEvent::KittySupported), len);
+        }
+        return (None, len);
+    }
+
+    // CSI code[:alternates] ; modifiers[:event] final
+    let mut fields = params.split(';');
+    let code: u32 = fields.next()
+        .and_then(|f| f.split(':').next())
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(1);
+    let mut modifier_field = fields.next().unwrap_or("1").split(':');
+    let modifiers: u32 = modifier_field.next().and_then(|m| m.parse().ok()).unwrap_or(1);
+    let action = match modifier_field.next() {
+        Some("2") => KeyAction::Repeat,
+        Some("3") => KeyAction::Release,
+        _ => KeyAction::Press,
+    };
+
+    let key = match bytes[end] {
+        b'u' => match code {
+            9 => KeyEvent::Char('\t'),
+            13 => KeyEvent::Char('\n'),
+            27 => KeyEvent::Esc,
+            127 => KeyEvent::Backspace,
+            _ => match char::from_u32(code) {
+                Some(c) if modifiers.saturating_sub(1) & 0b100 != 0 => KeyEvent::Ctrl(c),
+                Some(c) if modifiers.saturating_sub(1) & 0b010 != 0 => KeyEvent::Alt(c),
+                Some(c) if !c.is_control() && code < 0xE000 => KeyEvent::Char(c),
+                _ => return (None, len),
+            }
+        },
+        b'A' => KeyEvent::Up,
+        b'B' => KeyEvent::Down,
+        b'C' => KeyEvent::Right,
+        b'D' => KeyEvent::Left,
+        b'H' => KeyEvent::Home,
+        b'F' => KeyEvent::End,
+        b'Z' => KeyEvent::BackTab,
+        c @ b'P'..=b'S' => KeyEvent::F(1 + c - b'P'),
+        b'~' => match code {
+            1 | 7 => KeyEvent::Home,
+            2 => KeyEvent::Insert,
+            3 => KeyEvent::Delete,
+            4 | 8 => KeyEvent::End,
+            5 => KeyEvent::PageUp,
+            6 => KeyEvent::PageDown,
+            v @ 11..=15 => KeyEvent::F(v as u8 - 10),
+            v @ 17..=21 => KeyEvent::F(v as u8 - 11),
+            v @ 23..=24 => KeyEvent::F(v as u8 - 12),
+            _ => return (None, len),
+        },
+        _ => return (None, len),
+    };
+
+    (Some(TermEvent::Key(key, action)), len)
+}
+
+
+/// Parses `bytes` as a single byte ASCII char or a variable size UTF-8 char.
+fn parse_utf8_char(bytes: &[u8]) -> (Option<char>, usize) {
+    for len in 1..=bytes.len().min(4) {
+        if let Ok(s) = std::str::from_utf8(&bytes[..len]) {
+            return (s.chars().next(), len);
+        }
+    }
+    (None, 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn legacy_keys_test() {
+        let events = parse_events("aé\x03".as_bytes());
+        assert_eq!(events, vec![
+            TermEvent::Key(KeyEvent::Char('a'), KeyAction::Press),
+            TermEvent::Key(KeyEvent::Char('é'), KeyAction::Press),
+            TermEvent::Key(KeyEvent::Ctrl('c'), KeyAction::Press),
+        ]);
+    }
+
+    #[test]
+    pub fn kitty_keys_test() {
+        let events = parse_events(b"\x1b[?11u\x1b[97u\x1b[97;1:2u\x1b[97;1:3u\x1b[99;5u\x1b[15;1:3~");
+        assert_eq!(events, vec![
+            TermEvent::KittySupported,
+            TermEvent::Key(KeyEvent::Char('a'), KeyAction::Press),
+            TermEvent::Key(KeyEvent::Char('a'), KeyAction::Repeat),
+            TermEvent::Key(KeyEvent::Char('a'), KeyAction::Release),
+            TermEvent::Key(KeyEvent::Ctrl('c'), KeyAction::Press),
+            TermEvent::Key(KeyEvent::F(5), KeyAction::Release),
+        ]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+use chip8emu::keypad::{KeyHold, Keypad};
+use chip8emu::Keymap;
+use termkan::input::KeyEvent;
+use super::events::{EventReader, KeyAction, TermEvent};
+
+
+/// Emulator controls bound to function keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    Pause,    // F1
+    Advance,  // F2, run a single frame
+    Slower,   // F3
+    Faster,   // F4
+    Turbo,    // F5
+    Save,     // F6, to the selected save slot
+    Load,     // F7, from the selected save slot
+    Slot,     // F8, select the next save slot
+    Continue, // F9, debugger: continue, or pause while running
+    StepOver, // F10, debugger: run the next instruction, or the whole subroutine it calls
+    Step,     // F11, debugger: run the next instruction
+    StepOut,  // F12, debugger: run until the current subroutine returns
+}
+
+
+pub struct InputHandler {
+    events: EventReader,
+    hold: KeyHold,
+    keymap: Keymap,
+    arrows: Vec<(KeyEvent, u8)>, // arrow keys bound from the ROM's key hints
+    rewind: Option<Instant>,     // last press or repeat of Backspace, None once released
+    pub presses: Vec<KeyEvent>, // key presses received during the last update
+}
+
+
+impl InputHandler {
+    /// `key_hold` is how long a key stays down after its last press or auto-repeat when
+    /// the terminal does not report key releases.
+    pub fn new(keymap: Keymap, key_hold: Duration) -> InputHandler {
+        InputHandler {
+            events: EventReader::start(),
+            hold: KeyHold::new(key_hold),
+            keymap,
+            arrows: Vec::new(),
+            rewind: None,
+            presses: Vec::new(),
+        }
+    }
+
+    /// Binds the arrow keys to the hex keys of the `up`, `down`, `left` and `right` roles
+    /// of a ROM database entry.
+    pub fn set_key_hints(&mut self, keys: &[(String, u8)]) {
+        self.arrows = keys.iter()
+            .filter_map(|(role, code)| {
+                let key = match role.as_str() {
+                    "up" => KeyEvent::Up,
+                    "down" => KeyEvent::Down,
+                    "left" => KeyEvent::Left,
+                    "right" => KeyEvent::Right,
+                    _ => return None,
+                };
+                Some((key, *code))
+            })
+            .collect();
+    }
+
+    /// Processes every pending terminal event and writes the held keys into the CHIP-8 keypad.
+    pub fn update(&mut self, keypad: &mut Keypad) {
+        let now = Instant::now();
+        self.presses.clear();
+
+        while let Some(event) = self.events.poll() {
+            match event {
+                TermEvent::KittySupported => self.hold.set_release_events(true),
+                TermEvent::Key(key, action) => {
+                    let code = self.code_from_kkey(key);
+                    match action {
+                        KeyAction::Press | KeyAction::Repeat => {
+                            if action == KeyAction::Press {
+                                self.presses.push(key);
+                            }
+                            if key == KeyEvent::Backspace {
+                                self.rewind = Some(now);
+                            }
+                            if let Some(code) = code {
+                                self.hold.press(code, now);
+                            }
+                        }
+                        KeyAction::Release => {
+                            self.hold.set_release_events(true);
+                            if key == KeyEvent::Backspace {
+                                self.rewind = None;
+                            }
+                            if let Some(code) = code {
+                                self.hold.release(code);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.hold.apply(keypad, now);
+        // held like the keypad keys
+        if !self.hold.release_events() && self.rewind.is_some_and(|at| now.saturating_duration_since(at) > self.hold.hold) {
+            self.rewind = None;
+        }
+    }
+
+    /// true while Backspace, the rewind key, is held down.
+    pub fn is_rewinding(&self) -> bool {
+        self.rewind.is_some()
+    }
+
+    /// Hex key bound to a terminal key event, if any.
+    pub fn code_from_kkey(&self, key: KeyEvent) -> Option<u8> {
+        match key {
+            KeyEvent::Char(c) => self.keymap.code_for(c),
+            _ => self.arrows.iter().find(|(k, _)| *k == key).map(|(_, code)| *code)
+        }
+    }
+
+    /// Hotkeys pressed during the last update.
+    pub fn hotkeys(&self) -> impl Iterator<Item = Hotkey> + '_ {
+        self.presses.iter().filter_map(|key| match key {
+            KeyEvent::F(1) => Some(Hotkey::Pause),
+            KeyEvent::F(2) => Some(Hotkey::Advance),
+            KeyEvent::F(3) => Some(Hotkey::Slower),
+            KeyEvent::F(4) => Some(Hotkey::Faster),
+            KeyEvent::F(5) => Some(Hotkey::Turbo),
+            KeyEvent::F(6) => Some(Hotkey::Save),
+            KeyEvent::F(7) => Some(Hotkey::Load),
+            KeyEvent::F(8) => Some(Hotkey::Slot),
+            KeyEvent::F(9) => Some(Hotkey::Continue),
+            KeyEvent::F(10) => Some(Hotkey::StepOver),
+            KeyEvent::F(11) => Some(Hotkey::Step),
+            KeyEvent::F(12) => Some(Hotkey::StepOut),
+            _ => None
+        })
+    }
+
+    // Detects if Ctrl+C is pressed
+    pub fn should_quit(&self) -> bool {
+        self.presses.contains(&KeyEvent::Ctrl('c'))
+    }
+}
@@ -0,0 +1,8 @@
+//! Terminal frontend built on termkan.
+
+mod display;
+mod events;
+mod input;
+
+pub use display::Display;
+pub use input::{Hotkey, InputHandler};
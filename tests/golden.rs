@@ -0,0 +1,86 @@
+//! Runs the ROMs in `games/` headlessly and compares the final screen with the golden
+//! images in `tests/golden/`. Run with `UPDATE_GOLDEN=1` to rewrite them after an
+//! intended change, and review the diff.
+
+use std::env;
+use std::fs;
+use chip8emu::{Chip8, RomDatabase};
+use chip8emu::audio::NullAudio;
+use chip8emu::headless::{self, InputScript, Limits};
+use chip8emu::screenshot;
+
+
+const SEED: u64 = 0x8;
+const DEFAULT_IPF: u32 = 12;
+
+
+fn check_golden(rom: &str, frames: u64) {
+    let mut chip8 = Chip8::init();
+    let info = chip8.load_data(&format!("games/{}", rom), &RomDatabase::bundled(), None).unwrap();
+    chip8.seed_rng(SEED);
+    let ipf = info.and_then(|info| info.tickrate).unwrap_or(DEFAULT_IPF);
+
+    let limits = Limits { frames: Some(frames), ..Limits::default() };
+    headless::run(&mut chip8, ipf, &limits, &InputScript::default(), &mut NullAudio).unwrap();
+    let actual = screenshot::to_ascii(&chip8);
+
+    let path = format!("tests/golden/{}.txt", rom.trim_end_matches(".ch8"));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path, err));
+    assert!(actual == expected, "{} differs from {}:\n{}", rom, path, actual);
+}
+
+
+#[test]
+fn ibm_logo_test() {
+    check_golden("ibm_logo.ch8", 60);
+}
+
+#[test]
+fn test_opcode_test() {
+    check_golden("test_opcode.ch8", 60);
+}
+
+#[test]
+fn maze_test() {
+    check_golden("maze.ch8", 120);
+}
+
+#[test]
+fn particle_demo_test() {
+    check_golden("particle_demo.ch8", 300);
+}
+
+#[test]
+fn br8kout_test() {
+    check_golden("br8kout.ch8", 300);
+}
+
+#[test]
+fn flightrunner_test() {
+    check_golden("flightrunner.ch8", 300);
+}
+
+#[test]
+fn rockto_test() {
+    check_golden("rockto.ch8", 300);
+}
+
+#[test]
+fn octojam1title_test() {
+    check_golden("octojam1title.ch8", 300);
+}
+
+#[test]
+fn octoachip8story_test() {
+    check_golden("octoachip8story.ch8", 300);
+}
+
+#[test]
+fn rps_test() {
+    check_golden("RPS.ch8", 300);
+}